@@ -0,0 +1,132 @@
+use std::time::{Duration, Instant};
+
+use crate::engine::{AlphaBetaEngine, Engine, SearchLimits, SearchResult};
+use crate::Connect4;
+
+/// Positions of the bench, as the columns played from the empty board
+/// From the opening to the endgame, with quiet positions as well as forced wins
+pub const BENCH_POSITIONS: [&str; 13] = [
+    "",
+    "3",
+    "3332",
+    "2345543",
+    "0616225",
+    "23221243",
+    "3423443222",
+    "433304330012",
+    "01234560123456",
+    "3434124314520222",
+    "3343222331144556",
+    "21333523514501103035",
+    "640003335116310003342116",
+];
+
+/// Depth each bench position is searched to by default
+pub const DEFAULT_BENCH_DEPTH: i32 = 10;
+
+pub struct BenchResult {
+    /// Search of each position, in `BENCH_POSITIONS` order
+    pub searches: Vec<SearchResult>,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    pub fn nodes(&self) -> u64 {
+        self.searches.iter().map(|search| search.nodes).sum()
+    }
+
+    pub fn nodes_per_second(&self) -> u64 {
+        (self.nodes() as f64 / self.elapsed.as_secs_f64().max(1e-9)) as u64
+    }
+
+    /// Hash of the node counts and moves of every search, any change to the search shows up here
+    /// Changes that only make it faster keep the same signature
+    pub fn signature(&self) -> u64 {
+        // FNV-1a
+        self.searches.iter().fold(0xcbf2_9ce4_8422_2325, |hash, search| {
+            [search.nodes, search.best_move as u64]
+                .iter()
+                .fold(hash, |hash, &value| (hash ^ value).wrapping_mul(0x0100_0000_01b3))
+        })
+    }
+}
+
+pub fn position(moves: &str) -> Connect4 {
+    let mut game = Connect4::new();
+    for col in moves.chars() {
+        game.play(col.to_digit(10).unwrap());
+    }
+    game
+}
+
+/// Search every bench position to `depth`, each with a fresh single-threaded engine so the node
+/// counts only depend on the search itself
+pub fn run(depth: i32) -> BenchResult {
+    run_threads(depth, 1)
+}
+
+/// Same as `run` with `threads` search threads
+/// With more than one, node counts and signatures change from run to run, compare the time to depth
+pub fn run_threads(depth: i32, threads: usize) -> BenchResult {
+    let start = Instant::now();
+    let searches = BENCH_POSITIONS
+        .iter()
+        .map(|moves| {
+            let mut engine = AlphaBetaEngine::new(0);
+            engine.options.threads = threads;
+            engine.choose_move(&position(moves), &SearchLimits::depth(depth))
+        })
+        .collect();
+    BenchResult { searches, elapsed: start.elapsed() }
+}
+
+/// How much sooner `threads` threads reach `depth` than a single one, on the whole bench
+pub struct Speedup {
+    pub single: BenchResult,
+    pub parallel: BenchResult,
+}
+
+impl Speedup {
+    pub fn measure(depth: i32, threads: usize) -> Speedup {
+        Speedup { single: run(depth), parallel: run_threads(depth, threads) }
+    }
+
+    /// Time to depth of one thread over that of all of them
+    pub fn time_ratio(&self) -> f64 {
+        self.single.elapsed.as_secs_f64() / self.parallel.elapsed.as_secs_f64().max(1e-9)
+    }
+
+    /// Nodes of all the threads over those of one, the work the helpers add to get there
+    pub fn nodes_ratio(&self) -> f64 {
+        self.parallel.nodes() as f64 / self.single.nodes().max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_is_reproducible() {
+        for moves in BENCH_POSITIONS {
+            let game = position(moves);
+            assert_eq!(game.get_moves().len(), moves.len(), "illegal bench position {}", moves);
+            assert!(game.is_someone_winning().is_none() && !game.is_draw(), "finished bench position {}", moves);
+        }
+        let first = run(4);
+        let second = run(4);
+        assert_eq!(first.searches.len(), BENCH_POSITIONS.len());
+        assert!(first.nodes() > 0);
+        assert_eq!(first.signature(), second.signature());
+        assert_ne!(first.signature(), run(5).signature());
+    }
+
+    #[test]
+    fn test_threads_reach_depth() {
+        let speedup = Speedup::measure(4, 3);
+        assert_eq!(speedup.parallel.searches.len(), BENCH_POSITIONS.len());
+        // The helpers search too, their nodes are counted
+        assert!(speedup.nodes_ratio() > 1.0);
+        assert!(speedup.time_ratio() > 0.0);
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, Weak};
+
+use once_cell::sync::Lazy;
+use rand::seq::SliceRandom;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::EvalParams;
+use crate::evaluator;
+use crate::json;
+use crate::solver::{self, Solver};
+use crate::transposition::TranspositionTable;
+use crate::Connect4;
+
+/// A book move and its score for the player to move, on the alpha-beta scale
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BookMove {
+    pub col: u32,
+    pub score: i32,
+}
+
+/// How the book scores the moves of a position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BookSource {
+    /// Alpha-beta search to this depth
+    Search { depth: i32 },
+    /// Solver, with a node budget per move, falling back to a search of `depth` when it runs out
+    Solver { nodes: u64, depth: i32 },
+}
+
+/// Scored moves of the first plies, keyed on the canonical position hash so transpositions and
+/// mirror images share an entry
+/// Stored as JSON
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpeningBook {
+    /// Positions with fewer stones than this are in the book
+    pub plies: usize,
+    pub positions: BTreeMap<u64, Vec<BookMove>>,
+}
+
+// Books in use by engines, read once per file and dropped along with the last engine using them
+static BOOKS: Lazy<Mutex<HashMap<String, Weak<OpeningBook>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+impl OpeningBook {
+    /// Score every position reachable in fewer than `plies` plies, `verbose` printing the positions after each ply
+    pub fn generate(plies: usize, source: BookSource, verbose: bool) -> OpeningBook {
+        let mut book = OpeningBook { plies, positions: BTreeMap::new() };
+        let mut seen = HashSet::new();
+        let mut frontier = vec![Connect4::new()];
+        let table = TranspositionTable::default();
+        let mut solver = Solver::new();
+
+        for ply in 0..plies {
+            let mut next = Vec::new();
+            for mut game in frontier {
+                let (key, mirrored) = game.canonical_hash();
+                if game.is_someone_winning().is_some() || game.is_draw() || !seen.insert(key) {
+                    continue;
+                }
+                let moves = score_position(&mut game, source, &table, &mut solver);
+                for mv in &moves {
+                    let mut child = game.clone();
+                    child.play(mv.col);
+                    next.push(child);
+                }
+                book.positions.insert(key, oriented(&game, moves, mirrored));
+            }
+            if verbose {
+                println!("Ply {}: {} positions", ply, book.positions.len());
+            }
+            frontier = next;
+        }
+        book
+    }
+
+    pub fn load(path: &str) -> Result<OpeningBook, String> {
+        json::load(path, "book")
+    }
+
+    /// Same as `load`, sharing the book with every other engine using the same file
+    pub fn load_shared(path: &str) -> Result<Arc<OpeningBook>, String> {
+        let mut books = BOOKS.lock().unwrap();
+        if let Some(book) = books.get(path).and_then(Weak::upgrade) {
+            return Ok(book);
+        }
+        let book = Arc::new(OpeningBook::load(path)?);
+        books.retain(|_, book| book.strong_count() > 0);
+        books.insert(path.to_string(), Arc::downgrade(&book));
+        Ok(book)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        json::save(self, path, false)
+    }
+
+    /// Book moves of a position, best first
+    pub fn moves(&self, game: &Connect4) -> Option<Vec<BookMove>> {
+        let (key, mirrored) = game.canonical_hash();
+        self.positions.get(&key).map(|moves| oriented(game, moves.clone(), mirrored))
+    }
+
+    /// Pick one of the moves scoring at most `margin` below the best one, 0 always playing the best
+    pub fn pick(&self, game: &Connect4, margin: i32, rng: &mut dyn RngCore) -> Option<BookMove> {
+        let moves = self.moves(game)?;
+        let best = moves.first()?.score;
+        let candidates: Vec<&BookMove> = moves.iter().filter(|mv| mv.score >= best - margin).collect();
+        candidates.choose(rng).map(|&&mv| mv)
+    }
+}
+
+/// Moves of `game` as stored under its canonical hash, or the other way around
+fn oriented(game: &Connect4, moves: Vec<BookMove>, mirrored: bool) -> Vec<BookMove> {
+    if !mirrored {
+        return moves;
+    }
+    moves.into_iter().map(|mv| BookMove { col: game.mirror_move(mv.col), ..mv }).collect()
+}
+
+/// Score of every legal move of `game` for the player to move, best first
+pub(crate) fn score_position(game: &mut Connect4, source: BookSource, table: &TranspositionTable, solver: &mut Solver) -> Vec<BookMove> {
+    let (depth, solver_nodes) = match source {
+        BookSource::Search { depth } => (depth, None),
+        BookSource::Solver { nodes, depth } => (depth, Some(nodes)),
+    };
+
+    let solved = solver_nodes.and_then(|nodes| {
+        let moves = game.get_moves().len() as i32;
+        let mut scores = Vec::new();
+        for col in 0..7 {
+            let mut child = game.clone();
+            if !child.play(col) {
+                continue;
+            }
+            let score = match solver::terminal_score(&child) {
+                // The move ended the game, as a win or a draw
+                Some(_) if child.is_someone_winning().is_some() => evaluator::WIN_SCORE - moves - 1,
+                Some(_) => 0,
+                None => -solver::search_score(solver.try_solve(&child, nodes)?, moves + 1),
+            };
+            scores.push(BookMove { col, score });
+        }
+        Some(scores)
+    });
+
+    let mut moves = solved.unwrap_or_else(|| {
+        evaluator::score_moves(game, depth, table, &EvalParams::default())
+            .into_iter()
+            .map(|(col, score)| BookMove { col, score })
+            .collect()
+    });
+    moves.sort_by_key(|mv| std::cmp::Reverse(mv.score));
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::position;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_generate_and_pick() {
+        let book = OpeningBook::generate(2, BookSource::Search { depth: 2 }, false);
+        // The empty board and its 7 children, 4 of them up to mirroring
+        assert_eq!(book.positions.len(), 5);
+
+        let json = serde_json::to_string(&book).unwrap();
+        let loaded: OpeningBook = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, book);
+
+        let game = Connect4::new();
+        let best = book.moves(&game).unwrap()[0];
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(book.pick(&game, 0, &mut rng).unwrap().col, best.col);
+        assert!(book.pick(&position("33"), 0, &mut rng).is_none());
+
+        // A mirrored position gets the mirrored moves
+        let game = position("1");
+        let moves = book.moves(&game).unwrap();
+        let mirrored = book.moves(&game.mirror()).unwrap();
+        for (mv, mirror) in moves.iter().zip(&mirrored) {
+            assert_eq!((mirror.col, mirror.score), (6 - mv.col, mv.score));
+        }
+    }
+
+    #[test]
+    fn test_solver_source() {
+        // Red has three stacked in column 0, the solver scores the win exactly
+        let mut game = position("010101");
+        let moves = score_position(
+            &mut game,
+            BookSource::Solver { nodes: 100_000, depth: 2 },
+            &TranspositionTable::new(1),
+            &mut Solver::new(),
+        );
+        assert_eq!(moves[0], BookMove { col: 0, score: evaluator::WIN_SCORE - 7 });
+    }
+}
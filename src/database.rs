@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+
+use once_cell::sync::Lazy;
+
+use crate::Connect4;
+
+/// Exact value of a position, on the solver scale, and its best move when known
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SolvedPosition {
+    pub score: i32,
+    pub best_move: Option<u32>,
+}
+
+/// Bytes of a record: the position hash, the score and the best move (0xFF for none)
+const RECORD_SIZE: usize = 10;
+const NO_MOVE: u8 = 0xFF;
+
+/// Solved positions keyed on their canonical hash, shared by the solver and the alpha-beta search
+/// The file is an append-only list of records, a later record for a position replaces the earlier ones
+pub struct PositionDb {
+    path: Option<String>,
+    positions: RwLock<HashMap<u64, SolvedPosition>>,
+    /// Records not written to the file yet
+    pending: Mutex<Vec<(u64, SolvedPosition)>>,
+}
+
+// Databases in use by engines, read once per file and dropped along with the last engine using them
+static DATABASES: Lazy<Mutex<HashMap<String, Weak<PositionDb>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+impl PositionDb {
+    /// Database that is never written to disk
+    pub fn in_memory() -> PositionDb {
+        PositionDb {
+            path: None,
+            positions: RwLock::new(HashMap::new()),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Read the database at `path`, starting empty when the file does not exist yet
+    pub fn open(path: &str) -> Result<PositionDb, String> {
+        let mut positions = HashMap::new();
+        match std::fs::read(path) {
+            // A record cut short by an interrupted write is ignored
+            Ok(bytes) => {
+                for record in bytes.chunks_exact(RECORD_SIZE) {
+                    let key = u64::from_le_bytes(record[..8].try_into().unwrap());
+                    let best_move = (record[9] != NO_MOVE).then_some(record[9] as u32);
+                    positions.insert(key, SolvedPosition { score: record[8] as i8 as i32, best_move });
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Cannot read {}: {}", path, e)),
+        }
+        Ok(PositionDb {
+            path: Some(path.to_string()),
+            positions: RwLock::new(positions),
+            pending: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Same as `open`, sharing the database with every other engine using the same file
+    pub fn open_shared(path: &str) -> Result<Arc<PositionDb>, String> {
+        let mut databases = DATABASES.lock().unwrap();
+        if let Some(database) = databases.get(path).and_then(Weak::upgrade) {
+            return Ok(database);
+        }
+        let database = Arc::new(PositionDb::open(path)?);
+        databases.retain(|_, database| database.strong_count() > 0);
+        databases.insert(path.to_string(), Arc::downgrade(&database));
+        Ok(database)
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, key: u64) -> Option<SolvedPosition> {
+        self.positions.read().unwrap().get(&key).copied()
+    }
+
+    /// Record a solved position, only a best move can be added to a position already known
+    pub fn insert(&self, key: u64, solved: SolvedPosition) {
+        let mut positions = self.positions.write().unwrap();
+        if positions.get(&key).is_some_and(|known| known.best_move.is_some() || solved.best_move.is_none()) {
+            return;
+        }
+        positions.insert(key, solved);
+        if self.path.is_some() {
+            self.pending.lock().unwrap().push((key, solved));
+        }
+    }
+
+    /// Solved value of `game`, found under its own hash or its mirror image's
+    pub fn lookup(&self, game: &Connect4) -> Option<SolvedPosition> {
+        let (key, mirrored) = game.canonical_hash();
+        self.get(key).map(|solved| SolvedPosition {
+            best_move: solved.best_move.map(|col| if mirrored { game.mirror_move(col) } else { col }),
+            ..solved
+        })
+    }
+
+    /// Record `game` as solved, for its mirror image as well
+    pub fn record(&self, game: &Connect4, solved: SolvedPosition) {
+        let (key, mirrored) = game.canonical_hash();
+        let best_move = solved.best_move.map(|col| if mirrored { game.mirror_move(col) } else { col });
+        self.insert(key, SolvedPosition { best_move, ..solved });
+    }
+
+    /// Append the positions solved since the last flush to the file
+    pub fn flush(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut pending = self.pending.lock().unwrap();
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let mut bytes = Vec::with_capacity(pending.len() * RECORD_SIZE);
+        for (key, solved) in pending.iter() {
+            bytes.extend_from_slice(&key.to_le_bytes());
+            bytes.push(solved.score as i8 as u8);
+            bytes.push(solved.best_move.map_or(NO_MOVE, |col| col as u8));
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(&bytes))
+            .map_err(|e| format!("Cannot write {}: {}", path, e))?;
+        pending.clear();
+        Ok(())
+    }
+}
+
+impl Debug for PositionDb {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("PositionDb").field("path", &self.path).field("positions", &self.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_persists_across_opens() {
+        let path = std::env::temp_dir().join("connect4_test_positions.db");
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let database = PositionDb::open(path).unwrap();
+        assert!(database.is_empty());
+        database.insert(1, SolvedPosition { score: -3, best_move: None });
+        database.insert(2, SolvedPosition { score: 18, best_move: Some(0) });
+        database.flush().unwrap();
+        // A best move is added to a known position, a missing one doesn't erase it
+        database.insert(1, SolvedPosition { score: -3, best_move: Some(4) });
+        database.insert(1, SolvedPosition { score: -3, best_move: None });
+        database.flush().unwrap();
+
+        let reopened = PositionDb::open(path).unwrap();
+        assert_eq!(reopened.len(), 2);
+        assert_eq!(reopened.get(1), Some(SolvedPosition { score: -3, best_move: Some(4) }));
+        assert_eq!(reopened.get(2), Some(SolvedPosition { score: 18, best_move: Some(0) }));
+        assert_eq!(reopened.get(3), None);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_shared_until_unused() {
+        let path = std::env::temp_dir().join("connect4_test_shared.db");
+        let path = path.to_str().unwrap();
+        let first = PositionDb::open_shared(path).unwrap();
+        let second = PositionDb::open_shared(path).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        drop((first, second));
+        assert!(DATABASES.lock().unwrap().get(path).and_then(Weak::upgrade).is_none());
+    }
+
+    #[test]
+    fn test_mirror_shares_entry() {
+        let database = PositionDb::in_memory();
+        let mut game = Connect4::new();
+        for col in [0, 1] {
+            game.play(col);
+        }
+        database.record(&game, SolvedPosition { score: 2, best_move: Some(1) });
+        assert_eq!(database.len(), 1);
+        assert_eq!(database.lookup(&game), Some(SolvedPosition { score: 2, best_move: Some(1) }));
+        assert_eq!(database.lookup(&game.mirror()), Some(SolvedPosition { score: 2, best_move: Some(5) }));
+    }
+}
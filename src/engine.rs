@@ -0,0 +1,832 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+
+use crate::book::OpeningBook;
+use crate::database::PositionDb;
+use crate::evaluator;
+pub use crate::evaluator::{
+    is_decisive, plies_to_win, EvalParams, MoveOrdering, SearchAlgorithm, SearchOptions, StopHandle, WIN_SCORE,
+};
+use crate::mcts::{MctsEngine, Playout, DEFAULT_EXPLORATION};
+use crate::skill::{SkillEngine, SkillLevel};
+use crate::solver::{self, Solver};
+use crate::transposition::{TranspositionTable, DEFAULT_HASH_MB};
+use crate::Connect4;
+
+/// Names accepted by `engine_from_name`
+pub const ENGINE_NAMES: [&str; 11] = [
+    "minimax",
+    "random",
+    "greedy",
+    "solver",
+    "mcts",
+    "beginner",
+    "novice",
+    "intermediate",
+    "advanced",
+    "expert",
+    "master",
+];
+
+/// How much an engine may search for a move
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchLimits {
+    pub depth: i32,
+    pub time: Option<Duration>,
+    /// Number of iterations for engines that count them rather than depth
+    pub nodes: Option<u64>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: i32) -> SearchLimits {
+        SearchLimits { depth, time: None, nodes: None }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchResult {
+    pub best_move: u32,
+    /// Score of the best move for the player to move, on the `evaluate_board` scale
+    /// A win scores `WIN_SCORE` minus the plies of the game when it happens, see `plies_to_win`
+    pub score: i32,
+    /// Depth of the last completed search iteration
+    pub depth: i32,
+    /// Positions visited, 0 for engines that do not count them
+    pub nodes: u64,
+}
+
+/// Something able to pick a move for the player to move
+/// Engines are never asked to move in a finished game
+pub trait Engine: Send {
+    fn name(&self) -> &'static str;
+
+    fn choose_move(&mut self, game: &Connect4, limits: &SearchLimits) -> SearchResult;
+
+    /// Think on the opponent's time, `game` being the position they have to move in
+    /// Engines that can't ponder, or weren't asked to, do nothing
+    fn ponder(&mut self, _game: &Connect4, _limits: &SearchLimits) {}
+
+    /// Handle stopping `choose_move` from another thread, for engines that support it
+    fn stop_handle(&self) -> Option<StopHandle> {
+        None
+    }
+}
+
+/// The iterative deepening alpha-beta search from `evaluator`
+/// Its transposition table is kept from one move to the next
+pub struct AlphaBetaEngine {
+    pub options: SearchOptions,
+    /// Search the expected reply while the opponent thinks, see `Engine::ponder`
+    pub ponder: bool,
+    rng: StdRng,
+    table: Arc<TranspositionTable>,
+    pondering: Option<Ponder>,
+    ponder_hits: u64,
+}
+
+/// Background search of the position after the opponent's expected reply
+struct Ponder {
+    hash: u64,
+    stop: StopHandle,
+    search: JoinHandle<SearchResult>,
+}
+
+impl AlphaBetaEngine {
+    pub fn new(seed: u64) -> AlphaBetaEngine {
+        AlphaBetaEngine::with_hash(seed, DEFAULT_HASH_MB)
+    }
+
+    /// Engine with a transposition table of `hash_mb` megabytes
+    pub fn with_hash(seed: u64, hash_mb: usize) -> AlphaBetaEngine {
+        AlphaBetaEngine {
+            options: SearchOptions::default(),
+            ponder: false,
+            rng: StdRng::seed_from_u64(seed),
+            table: Arc::new(TranspositionTable::new(hash_mb)),
+            pondering: None,
+            ponder_hits: 0,
+        }
+    }
+
+    /// Moves answered by the ponder search alone, the opponent having played the expected reply
+    pub fn ponder_hits(&self) -> u64 {
+        self.ponder_hits
+    }
+
+    /// Last completed iteration of the ponder search if it searched `game`
+    /// Its table entries are kept either way, so a search of another reply still starts warm
+    fn stop_pondering(&mut self, game: &Connect4) -> Option<SearchResult> {
+        let ponder = self.pondering.take()?;
+        ponder.stop.stop();
+        let result = ponder.search.join().unwrap();
+        (ponder.hash == game.get_hash()).then_some(result)
+    }
+}
+
+impl Drop for AlphaBetaEngine {
+    fn drop(&mut self) {
+        if let Some(ponder) = &self.pondering {
+            ponder.stop.stop();
+        }
+    }
+}
+
+impl Engine for AlphaBetaEngine {
+    fn name(&self) -> &'static str {
+        "minimax"
+    }
+
+    fn choose_move(&mut self, game: &Connect4, limits: &SearchLimits) -> SearchResult {
+        // A ponder search deep enough, or that found a win, answers right away
+        // A shallower one is deepened from where it stopped
+        let pondered = self.stop_pondering(game);
+        let answered =
+            |result: &SearchResult| result.depth >= limits.depth || (is_decisive(result.score) && result.score > 0);
+        if let Some(result) = pondered.filter(answered) {
+            self.ponder_hits += 1;
+            return result;
+        }
+        let resumed = pondered.map(|result| SearchOptions { resume: Some(result), ..self.options.clone() });
+        let options = resumed.as_ref().unwrap_or(&self.options);
+        let mut game = game.clone();
+        let result =
+            evaluator::search_with_options(&mut game, limits.depth, limits.time, &mut self.rng, &self.table, options);
+        flush_database(self.options.database.as_deref());
+        result
+    }
+
+    /// Searches the position after the reply the last search expects, until the opponent moves
+    /// The time limit is ignored, the search goes as deep as `limits.depth` allows and the next
+    /// `choose_move` goes on from its last completed iteration
+    fn ponder(&mut self, game: &Connect4, limits: &SearchLimits) {
+        self.stop_pondering(game);
+        if !self.ponder || game.is_someone_winning().is_some() || game.is_draw() {
+            return;
+        }
+        let Some(reply) = evaluator::table_move(&self.table, game) else {
+            return;
+        };
+        let mut expected = game.clone();
+        if !expected.play(reply) || expected.is_someone_winning().is_some() || expected.is_draw() {
+            return;
+        }
+
+        // A stop handle of its own, stopping the ponder search doesn't stop the next one
+        let options = SearchOptions { stop: StopHandle::new(), ..self.options.clone() };
+        let stop = options.stop.clone();
+        let table = self.table.clone();
+        let mut rng = StdRng::seed_from_u64(self.rng.gen());
+        let depth = limits.depth;
+        let hash = expected.get_hash();
+        let search = thread::spawn(move || {
+            evaluator::search_with_options(&mut expected, depth, None, &mut rng, &table, &options)
+        });
+        self.pondering = Some(Ponder { hash, stop, search });
+    }
+
+    fn stop_handle(&self) -> Option<StopHandle> {
+        Some(self.options.stop.clone())
+    }
+}
+
+/// Plays any legal move
+pub struct RandomEngine {
+    rng: StdRng,
+}
+
+impl RandomEngine {
+    pub fn new(seed: u64) -> RandomEngine {
+        RandomEngine { rng: StdRng::seed_from_u64(seed) }
+    }
+}
+
+impl Engine for RandomEngine {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn choose_move(&mut self, game: &Connect4, _limits: &SearchLimits) -> SearchResult {
+        let moves: Vec<u32> = game.legal_moves().collect();
+        SearchResult {
+            best_move: *moves.choose(&mut self.rng).unwrap(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+        }
+    }
+}
+
+/// Plays the move with the best static evaluation, looking only one ply ahead
+#[derive(Default)]
+pub struct GreedyEngine {
+    pub eval: EvalParams,
+}
+
+impl Engine for GreedyEngine {
+    fn name(&self) -> &'static str {
+        "greedy"
+    }
+
+    fn choose_move(&mut self, game: &Connect4, _limits: &SearchLimits) -> SearchResult {
+        let mut game = game.clone();
+        let mut best = SearchResult {
+            best_move: 0,
+            score: i32::MIN,
+            depth: 1,
+            nodes: 0,
+        };
+        for col in [3, 2, 4, 1, 5, 0, 6] {
+            if game.play(col) {
+                best.nodes += 1;
+                // The evaluation is from the opponent's point of view once the move is played
+                let score = -evaluator::evaluate_board(&game, &self.eval);
+                game.undo().unwrap();
+                if score > best.score {
+                    best.best_move = col;
+                    best.score = score;
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Perfect play once the position is small enough to be solved
+/// Earlier in the game it falls back to the alpha-beta search
+pub struct SolverEngine {
+    solver: Solver,
+    fallback: AlphaBetaEngine,
+}
+
+impl SolverEngine {
+    pub fn new(seed: u64) -> SolverEngine {
+        SolverEngine::with_hash(seed, DEFAULT_HASH_MB)
+    }
+
+    /// `hash_mb` is the size of the fallback search's transposition table
+    pub fn with_hash(seed: u64, hash_mb: usize) -> SolverEngine {
+        SolverEngine {
+            solver: Solver::new(),
+            fallback: AlphaBetaEngine::with_hash(seed, hash_mb),
+        }
+    }
+
+    /// Share solved positions with `database`, in the solver and the fallback search
+    pub fn set_database(&mut self, database: Arc<PositionDb>) {
+        self.solver.set_database(database.clone());
+        self.fallback.options.database = Some(database);
+    }
+}
+
+/// Positions needing more nodes than this are left to the alpha-beta search
+const SOLVER_NODE_LIMIT: u64 = 2_000_000;
+
+impl Engine for SolverEngine {
+    fn name(&self) -> &'static str {
+        "solver"
+    }
+
+    fn choose_move(&mut self, game: &Connect4, limits: &SearchLimits) -> SearchResult {
+        let nodes = self.solver.get_nodes();
+        match self.solver.try_best_move(game, SOLVER_NODE_LIMIT) {
+            Some((best_move, score)) => {
+                flush_database(self.fallback.options.database.as_deref());
+                let moves = game.get_moves().len() as i32;
+                SearchResult {
+                    best_move,
+                    score: solver::search_score(score, moves),
+                    depth: solver::plies_to_end(score, moves),
+                    nodes: self.solver.get_nodes() - nodes,
+                }
+            }
+            None => self.fallback.choose_move(game, limits),
+        }
+    }
+
+    /// Stops the fallback search, the solver itself runs on a node budget
+    fn stop_handle(&self) -> Option<StopHandle> {
+        self.fallback.stop_handle()
+    }
+}
+
+// Positions solved during a move are saved right away, a failed write only costs them
+fn flush_database(database: Option<&PositionDb>) {
+    if let Err(error) = database.map_or(Ok(()), PositionDb::flush) {
+        eprintln!("Error: {}", error);
+    }
+}
+
+/// Build an engine from its name, `seed` drives all of its random choices
+pub fn engine_from_name(name: &str, seed: u64) -> Option<Box<dyn Engine>> {
+    match name {
+        "minimax" => Some(Box::new(AlphaBetaEngine::new(seed))),
+        "random" => Some(Box::new(RandomEngine::new(seed))),
+        "greedy" => Some(Box::new(GreedyEngine::default())),
+        "solver" => Some(Box::new(SolverEngine::new(seed))),
+        "mcts" => Some(Box::new(MctsEngine::new(seed))),
+        _ => SkillLevel::from_name(name).map(|level| Box::new(SkillEngine::new(level, seed)) as Box<dyn Engine>),
+    }
+}
+
+/// Derive an independent seed, e.g. one per game of a seeded tournament
+pub fn derive_seed(seed: u64, stream: u64) -> u64 {
+    // SplitMix64 finalizer
+    let mut z = seed ^ stream.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// An engine and its search limits
+/// Written as `minimax:depth=4,time=100`, every setting being optional
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct EngineConfig {
+    #[serde(default = "default_engine")]
+    pub engine: String,
+    pub depth: Option<i32>,
+    pub time_ms: Option<u64>, // Time budget per move
+    pub nodes: Option<u64>, // Iteration budget per move, for MCTS
+    pub exploration: Option<f64>, // MCTS exploration constant
+    pub playout: Option<Playout>, // MCTS playout policy
+    pub seed: Option<u64>, // Seed of the engine's random choices, random when unset
+    pub hash_mb: Option<usize>, // Transposition table size in MB, for the alpha-beta based engines
+    pub threads: Option<usize>, // Search threads of minimax
+    pub ordering: Option<MoveOrdering>, // Move ordering of minimax, dynamic by default
+    pub algorithm: Option<SearchAlgorithm>, // Search windows of minimax, pvs by default
+    pub eval: Option<EvalParams>, // Evaluation weights of minimax, greedy and the skill levels
+    #[serde(skip)] // Server paths are not for clients to name, only the command line sets it
+    pub eval_file: Option<String>, // JSON file to read the evaluation weights from, when `eval` is unset
+    #[serde(skip)]
+    pub book: Option<String>, // Opening book file of minimax
+    pub book_margin: Option<i32>, // Book moves up to this much worse than the best are played too, 0 by default
+    #[serde(skip)]
+    pub database: Option<String>, // Solved positions file of minimax and solver, created when missing
+    pub ponder: Option<bool>, // Whether minimax searches the expected reply on the opponent's time, off by default
+    #[serde(skip)] // Writes to the log of the server, only the command line sets it
+    pub verbose: Option<bool>, // Whether minimax prints its root move values and search timers, off by default
+}
+
+fn default_engine() -> String {
+    "minimax".to_string()
+}
+
+impl EngineConfig {
+    pub fn new(engine: &str) -> EngineConfig {
+        EngineConfig {
+            engine: engine.to_string(),
+            depth: None,
+            time_ms: None,
+            nodes: None,
+            exploration: None,
+            playout: None,
+            seed: None,
+            hash_mb: None,
+            threads: None,
+            ordering: None,
+            algorithm: None,
+            eval: None,
+            eval_file: None,
+            book: None,
+            book_margin: None,
+            database: None,
+            ponder: None,
+            verbose: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !ENGINE_NAMES.contains(&self.engine.as_str()) {
+            return Err(format!("Unknown engine type: {}", self.engine));
+        }
+        if self.depth.is_some_and(|depth| depth < 1) {
+            return Err("Engine depth must be at least 1".to_string());
+        }
+        if self.nodes == Some(0) {
+            return Err("Engine nodes must be at least 1".to_string());
+        }
+        if self.exploration.is_some_and(|exploration| exploration < 0.0) {
+            return Err("Exploration constant must be positive".to_string());
+        }
+        if self.hash_mb == Some(0) {
+            return Err("Hash size must be at least 1 MB".to_string());
+        }
+        if self.threads == Some(0) {
+            return Err("Engine threads must be at least 1".to_string());
+        }
+        self.eval_params()?.validate()?;
+        if let Some(book) = &self.book {
+            OpeningBook::load_shared(book)?;
+        }
+        if self.book_margin.is_some_and(|margin| margin < 0) {
+            return Err("Book margin must be positive".to_string());
+        }
+        if let Some(database) = &self.database {
+            PositionDb::open_shared(database)?;
+        }
+        Ok(())
+    }
+
+    /// Depth and time budget used for each move
+    /// With only a time budget the search deepens until the time runs out
+    pub fn limits(&self) -> SearchLimits {
+        let time = self.time_ms.map(Duration::from_millis);
+        let depth = match (self.depth, time) {
+            (Some(depth), _) => depth,
+            (None, Some(_)) => 42,
+            (None, None) => 5,
+        };
+        SearchLimits { depth, time, nodes: self.nodes }
+    }
+
+    /// Evaluation weights, from `eval`, `eval_file` or the defaults
+    pub fn eval_params(&self) -> Result<EvalParams, String> {
+        match (self.eval, &self.eval_file) {
+            (Some(eval), _) => Ok(eval),
+            (None, Some(path)) => EvalParams::load(path),
+            (None, None) => Ok(EvalParams::default()),
+        }
+    }
+
+    /// Same config, using `seed` unless it already has one
+    pub fn or_seed(&self, seed: u64) -> EngineConfig {
+        EngineConfig {
+            seed: Some(self.seed.unwrap_or(seed)),
+            ..self.clone()
+        }
+    }
+
+    pub fn build(&self) -> Result<Box<dyn Engine>, String> {
+        self.validate()?;
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let hash_mb = self.hash_mb.unwrap_or(DEFAULT_HASH_MB);
+        let eval = self.eval_params()?;
+        let database = self.database.as_deref().map(PositionDb::open_shared).transpose()?;
+        match self.engine.as_str() {
+            "mcts" => {
+                let mut engine = MctsEngine::new(seed);
+                engine.exploration = self.exploration.unwrap_or(DEFAULT_EXPLORATION);
+                engine.playout = self.playout.unwrap_or(Playout::Random);
+                Ok(Box::new(engine))
+            }
+            "minimax" => {
+                let mut engine = AlphaBetaEngine::with_hash(seed, hash_mb);
+                engine.options.threads = self.threads.unwrap_or(1);
+                engine.options.ordering = self.ordering.unwrap_or(MoveOrdering::Dynamic);
+                engine.options.algorithm = self.algorithm.unwrap_or(SearchAlgorithm::Pvs);
+                engine.options.eval = eval;
+                engine.options.book = self.book.as_deref().map(OpeningBook::load_shared).transpose()?;
+                engine.options.book_margin = self.book_margin.unwrap_or(0);
+                engine.options.database = database;
+                engine.ponder = self.ponder.unwrap_or(false);
+                engine.options.verbose = self.verbose.unwrap_or(false);
+                Ok(Box::new(engine))
+            }
+            "greedy" => Ok(Box::new(GreedyEngine { eval })),
+            "solver" => {
+                let mut engine = SolverEngine::with_hash(seed, hash_mb);
+                if let Some(database) = database {
+                    engine.set_database(database);
+                }
+                Ok(Box::new(engine))
+            }
+            name => match SkillLevel::from_name(name) {
+                Some(level) => {
+                    let mut engine = SkillEngine::with_hash(level, seed, hash_mb);
+                    engine.eval = eval;
+                    Ok(Box::new(engine))
+                }
+                None => Ok(engine_from_name(name, seed).unwrap()),
+            },
+        }
+    }
+}
+
+impl FromStr for EngineConfig {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (engine, settings) = spec.split_once(':').unwrap_or((spec, ""));
+        let mut config = EngineConfig::new(engine);
+        for setting in settings.split(',').filter(|s| !s.is_empty()) {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid engine setting: {}", setting))?;
+            match key {
+                "depth" => config.depth = Some(value.parse().map_err(|_| format!("Invalid depth: {}", value))?),
+                "time" => config.time_ms = Some(value.parse().map_err(|_| format!("Invalid time: {}", value))?),
+                "nodes" => config.nodes = Some(value.parse().map_err(|_| format!("Invalid nodes: {}", value))?),
+                "c" => config.exploration = Some(value.parse().map_err(|_| format!("Invalid exploration: {}", value))?),
+                "playout" => config.playout = Some(value.parse()?),
+                "seed" => config.seed = Some(value.parse().map_err(|_| format!("Invalid seed: {}", value))?),
+                "hash" => config.hash_mb = Some(value.parse().map_err(|_| format!("Invalid hash size: {}", value))?),
+                "threads" => config.threads = Some(value.parse().map_err(|_| format!("Invalid threads: {}", value))?),
+                "ordering" => config.ordering = Some(value.parse()?),
+                "algorithm" => config.algorithm = Some(value.parse()?),
+                "eval" => config.eval_file = Some(value.to_string()),
+                "book" => config.book = Some(value.to_string()),
+                "db" => config.database = Some(value.to_string()),
+                "ponder" => config.ponder = Some(value.parse().map_err(|_| format!("Invalid ponder: {}", value))?),
+                "verbose" => config.verbose = Some(value.parse().map_err(|_| format!("Invalid verbose: {}", value))?),
+                "book_margin" => config.book_margin = Some(value.parse().map_err(|_| format!("Invalid book margin: {}", value))?),
+                _ => return Err(format!("Unknown engine setting: {}", key)),
+            }
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl Display for EngineConfig {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.engine)?;
+        let mut settings = Vec::new();
+        if let Some(depth) = self.depth {
+            settings.push(format!("depth={}", depth));
+        }
+        if let Some(time_ms) = self.time_ms {
+            settings.push(format!("time={}", time_ms));
+        }
+        if let Some(nodes) = self.nodes {
+            settings.push(format!("nodes={}", nodes));
+        }
+        if let Some(exploration) = self.exploration {
+            settings.push(format!("c={}", exploration));
+        }
+        if let Some(playout) = self.playout {
+            settings.push(format!("playout={}", format!("{:?}", playout).to_lowercase()));
+        }
+        if let Some(seed) = self.seed {
+            settings.push(format!("seed={}", seed));
+        }
+        if let Some(hash_mb) = self.hash_mb {
+            settings.push(format!("hash={}", hash_mb));
+        }
+        if let Some(threads) = self.threads {
+            settings.push(format!("threads={}", threads));
+        }
+        if let Some(ordering) = self.ordering {
+            settings.push(format!("ordering={}", format!("{:?}", ordering).to_lowercase()));
+        }
+        if let Some(algorithm) = self.algorithm {
+            settings.push(format!("algorithm={}", format!("{:?}", algorithm).to_lowercase()));
+        }
+        if let Some(eval_file) = &self.eval_file {
+            settings.push(format!("eval={}", eval_file));
+        }
+        if let Some(book) = &self.book {
+            settings.push(format!("book={}", book));
+        }
+        if let Some(book_margin) = self.book_margin {
+            settings.push(format!("book_margin={}", book_margin));
+        }
+        if let Some(database) = &self.database {
+            settings.push(format!("db={}", database));
+        }
+        if let Some(ponder) = self.ponder {
+            settings.push(format!("ponder={}", ponder));
+        }
+        if let Some(verbose) = self.verbose {
+            settings.push(format!("verbose={}", verbose));
+        }
+        if !settings.is_empty() {
+            write!(f, ":{}", settings.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: EngineConfig = "minimax:depth=3,time=100".parse().unwrap();
+        assert_eq!(config.limits().depth, 3);
+        assert_eq!(config.limits().time, Some(Duration::from_millis(100)));
+        assert_eq!(config.to_string(), "minimax:depth=3,time=100");
+        assert!("greedy".parse::<EngineConfig>().is_ok());
+        let config: EngineConfig = "mcts:nodes=500,c=1.5,playout=heuristic".parse().unwrap();
+        assert_eq!(config.limits().nodes, Some(500));
+        assert_eq!(config.to_string(), "mcts:nodes=500,c=1.5,playout=heuristic");
+        assert!("alphazero".parse::<EngineConfig>().is_err());
+        assert!("minimax:depth=0".parse::<EngineConfig>().is_err());
+        let config: EngineConfig = "solver:hash=4".parse().unwrap();
+        assert_eq!(config.hash_mb, Some(4));
+        assert_eq!(config.to_string(), "solver:hash=4");
+        assert!("minimax:hash=0".parse::<EngineConfig>().is_err());
+        assert!("minimax:threads=0".parse::<EngineConfig>().is_err());
+        assert!("minimax:eval=missing.json".parse::<EngineConfig>().is_err());
+        let config: EngineConfig = serde_json::from_str(r#"{ "eval": { "threat": 2000000000 } }"#).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_json_config_names_no_files() {
+        let config: EngineConfig = serde_json::from_str(r#"{ "eval_file": "/etc/passwd" }"#).unwrap();
+        assert_eq!(config.eval_file, None);
+        let config: EngineConfig = serde_json::from_str(r#"{ "book": "/etc/passwd" }"#).unwrap();
+        assert_eq!(config.book, None);
+        let config: EngineConfig = serde_json::from_str(r#"{ "database": "/etc/passwd" }"#).unwrap();
+        assert_eq!(config.database, None);
+        let config: EngineConfig = serde_json::from_str(r#"{ "verbose": true }"#).unwrap();
+        assert_eq!(config.verbose, None);
+    }
+
+    #[test]
+    fn test_engines_take_immediate_win() {
+        let mut game = Connect4::new();
+        for col in [0, 1, 0, 1, 0, 1] {
+            game.play(col);
+        }
+        for name in ["greedy", "solver"] {
+            let mut engine = engine_from_name(name, 0).unwrap();
+            let result = engine.choose_move(&game, &SearchLimits::depth(3));
+            assert_eq!(result.best_move, 0, "{} missed the win", name);
+        }
+    }
+
+    #[test]
+    fn test_seeded_engines_replay() {
+        let game = Connect4::new();
+        for name in ["minimax", "random", "mcts", "beginner"] {
+            let config: EngineConfig = format!("{}:depth=2,nodes=200,seed=42", name).parse().unwrap();
+            let first: Vec<u32> = (0..5)
+                .scan(config.build().unwrap(), |engine, _| Some(engine.choose_move(&game, &config.limits()).best_move))
+                .collect();
+            let second: Vec<u32> = (0..5)
+                .scan(config.build().unwrap(), |engine, _| Some(engine.choose_move(&game, &config.limits()).best_move))
+                .collect();
+            assert_eq!(first, second, "{} is not reproducible", name);
+        }
+    }
+
+    #[test]
+    fn test_parallel_search_finds_win() {
+        let mut game = Connect4::new();
+        for col in [3, 3, 4, 4] {
+            game.play(col);
+        }
+        // Red wins by playing either end of its open three
+        let config: EngineConfig = "minimax:depth=4,threads=3,seed=1".parse().unwrap();
+        let result = config.build().unwrap().choose_move(&game, &config.limits());
+        assert_eq!(plies_to_win(result.score, 4), Some(3));
+        assert!([2, 5].contains(&result.best_move));
+    }
+
+    #[test]
+    fn test_dynamic_ordering_searches_fewer_nodes() {
+        let mut game = Connect4::new();
+        for col in [3, 2, 3, 4, 2] {
+            game.play(col);
+        }
+        let nodes = |spec: &str| {
+            let config: EngineConfig = spec.parse().unwrap();
+            config.build().unwrap().choose_move(&game, &config.limits()).nodes
+        };
+        let static_nodes = nodes("minimax:depth=7,ordering=static,seed=0");
+        let dynamic_nodes = nodes("minimax:depth=7,ordering=dynamic,seed=0");
+        assert!(dynamic_nodes < static_nodes, "{} >= {}", dynamic_nodes, static_nodes);
+    }
+
+    #[test]
+    fn test_minimax_plays_book_moves() {
+        let path = std::env::temp_dir().join("connect4_test_book.json");
+        let path = path.to_str().unwrap();
+        crate::book::OpeningBook::generate(1, crate::book::BookSource::Search { depth: 2 }, false).save(path).unwrap();
+
+        let config: EngineConfig = format!("minimax:depth=4,book={},book_margin=5", path).parse().unwrap();
+        assert_eq!(config.to_string(), format!("minimax:depth=4,book={},book_margin=5", path));
+        let mut engine = config.build().unwrap();
+        // Searched positions report their depth, book ones don't
+        assert_eq!(engine.choose_move(&Connect4::new(), &config.limits()).depth, 0);
+        let mut game = Connect4::new();
+        game.play(3);
+        assert_eq!(engine.choose_move(&game, &config.limits()).depth, 4);
+        assert!("minimax:book=missing.json".parse::<EngineConfig>().is_err());
+        assert!(format!("minimax:book={},book_margin=-1", path).parse::<EngineConfig>().is_err());
+    }
+
+    #[test]
+    fn test_minimax_shares_solved_positions() {
+        let path = std::env::temp_dir().join("connect4_test_engine.db");
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let mut game = Connect4::new();
+        for col in [0, 1, 0, 1, 0, 1] {
+            game.play(col);
+        }
+
+        let config: EngineConfig = format!("minimax:depth=4,seed=1,db={}", path).parse().unwrap();
+        assert_eq!(config.to_string(), format!("minimax:depth=4,seed=1,db={}", path));
+        let first = config.build().unwrap().choose_move(&game, &config.limits());
+        assert_eq!(plies_to_win(first.score, 6), Some(1));
+        assert!(std::fs::metadata(path).unwrap().len() > 0);
+
+        // The proven win is answered without searching, by any engine using the file
+        let second = config.build().unwrap().choose_move(&game, &config.limits());
+        assert_eq!((second.best_move, second.score, second.nodes), (first.best_move, first.score, 0));
+        let config: EngineConfig = format!("solver:db={}", path).parse().unwrap();
+        assert_eq!(config.build().unwrap().choose_move(&game, &config.limits()).nodes, 0);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_pvs_matches_alpha_beta_with_fewer_nodes() {
+        let mut game = Connect4::new();
+        for col in [3, 2, 3, 4, 2] {
+            game.play(col);
+        }
+        let search = |spec: &str| {
+            let config: EngineConfig = spec.parse().unwrap();
+            config.build().unwrap().choose_move(&game, &config.limits())
+        };
+        let alpha_beta = search("minimax:depth=8,algorithm=alphabeta,seed=0");
+        let pvs = search("minimax:depth=8,algorithm=pvs,seed=0");
+        assert_eq!(pvs.score, alpha_beta.score);
+        assert!(pvs.nodes < alpha_beta.nodes, "{} >= {}", pvs.nodes, alpha_beta.nodes);
+    }
+
+    #[test]
+    fn test_stop_handle_interrupts_search() {
+        let config: EngineConfig = "minimax:depth=30,seed=0".parse().unwrap();
+        let mut engine = config.build().unwrap();
+        let stop = engine.stop_handle().unwrap();
+        // Stopped before it starts, only the first iteration runs
+        stop.stop();
+        let result = engine.choose_move(&Connect4::new(), &config.limits());
+        assert_eq!(result.depth, 1);
+        assert!(!stop.is_stopped());
+
+        let search = thread::spawn(move || engine.choose_move(&Connect4::new(), &config.limits()));
+        thread::sleep(Duration::from_millis(200));
+        stop.stop();
+        let result = search.join().unwrap();
+        assert!(result.depth > 1 && result.depth < 30);
+    }
+
+    #[test]
+    fn test_ponder_hit_reuses_search() {
+        let mut engine = AlphaBetaEngine::new(0);
+        engine.ponder = true;
+        let limits = SearchLimits::depth(6);
+        let mut game = Connect4::new();
+        game.play_engine(&mut engine, &limits);
+        engine.ponder(&game, &limits);
+        let expected = evaluator::table_move(&engine.table, &game).unwrap();
+        while !engine.pondering.as_ref().unwrap().search.is_finished() {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        game.play(expected);
+        let result = engine.choose_move(&game, &limits);
+        assert_eq!(engine.ponder_hits(), 1);
+        assert_eq!(result.depth, 6);
+
+        // Any other reply is searched as usual
+        game.play(result.best_move);
+        engine.ponder(&game, &limits);
+        let expected = evaluator::table_move(&engine.table, &game).unwrap();
+        game.play((expected + 1) % 7);
+        engine.choose_move(&game, &limits);
+        assert_eq!(engine.ponder_hits(), 1);
+    }
+
+    #[test]
+    fn test_shallow_ponder_is_deepened() {
+        let mut engine = AlphaBetaEngine::new(0);
+        engine.ponder = true;
+        let mut game = Connect4::new();
+        game.play_engine(&mut engine, &SearchLimits::depth(4));
+        engine.ponder(&game, &SearchLimits::depth(4));
+        let expected = evaluator::table_move(&engine.table, &game).unwrap();
+        while !engine.pondering.as_ref().unwrap().search.is_finished() {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // Too shallow to answer a deeper search, which goes on from it
+        game.play(expected);
+        let result = engine.choose_move(&game, &SearchLimits::depth(6));
+        assert_eq!((engine.ponder_hits(), result.depth), (0, 6));
+    }
+
+    #[test]
+    fn test_random_engine_plays_legal_moves() {
+        let mut game = Connect4::new();
+        for _ in 0..6 {
+            game.play(3);
+        }
+        let mut engine = RandomEngine::new(0);
+        for _ in 0..20 {
+            assert_ne!(engine.choose_move(&game, &SearchLimits::depth(1)).best_move, 3);
+        }
+    }
+}
@@ -1,32 +1,126 @@
-use rand::Rng;
+use rand::{Rng, RngCore};
 use rustbenchmarktimer::timer::BenchmarkTimer;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::book::OpeningBook;
+use crate::database::{PositionDb, SolvedPosition};
+use crate::engine::SearchResult;
+use crate::json;
+use crate::solver::{self, column_mask, Position, EVEN_ROWS, ODD_ROWS};
+use crate::transposition::{NodeType, TranspositionTable};
 use crate::Connect4;
 
-// Added enum to represent node types for transposition table
-#[derive(Clone, Copy, PartialEq)]
-enum NodeType {
-    Exact,
-    LowerBound,
-    UpperBound,
+/// Score of a win, before taking off the plies played when it happened
+pub const WIN_SCORE: i32 = 1000;
+
+/// Scores from this value on are wins, so faster wins score higher
+const WIN_THRESHOLD: i32 = WIN_SCORE - 42;
+
+/// Largest heuristic score, any more would read as a forced result
+const MAX_HEURISTIC: i32 = WIN_THRESHOLD - 1;
+
+/// Whether `score` is a forced win or loss rather than an estimate
+pub fn is_decisive(score: i32) -> bool {
+    score.abs() >= WIN_THRESHOLD
 }
 
-// Added struct to store more detailed information in the transposition table
-struct TranspositionEntry {
-    score: i32,
-    depth: i32,
-    node_type: NodeType,
+/// Plies left until the winning move of a decisive score, `moves` being the plies already played
+/// Positive when the player to move wins, negative when they lose
+pub fn plies_to_win(score: i32, moves: usize) -> Option<i32> {
+    is_decisive(score).then(|| score.signum() * (WIN_SCORE - score.abs() - moves as i32))
+}
+
+/// Weights of the heuristic evaluation, every missing field keeps its default
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvalParams {
+    /// Per stone of the player to move in a line free of the opponent's stones
+    pub line_stone: i32,
+    /// Per three-in-a-row with an empty end
+    pub threat: i32,
+    /// When two of those threats are stacked in a column
+    pub stacked_threats: i32,
+    /// Per threat on a row that suits its owner: odd rows for red, even rows for yellow
+    pub good_threat: i32,
+    /// Per threat on any other row
+    pub other_threat: i32,
+    /// For the player whose threats should win the endgame once the board fills up
+    pub zugzwang_control: i32,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams {
+            line_stone: 1,
+            threat: 10,
+            stacked_threats: 20,
+            good_threat: 15,
+            other_threat: 5,
+            zugzwang_control: 40,
+        }
+    }
 }
 
-// Replace simple HashMap with a more structured transposition table
-type TranspositionTable = HashMap<u64, TranspositionEntry>;
+impl EvalParams {
+    /// Number of weights
+    pub const WEIGHTS: usize = 6;
+
+    /// Every weight, in declaration order
+    pub fn weights_mut(&mut self) -> [&mut i32; EvalParams::WEIGHTS] {
+        [
+            &mut self.line_stone,
+            &mut self.threat,
+            &mut self.stacked_threats,
+            &mut self.good_threat,
+            &mut self.other_threat,
+            &mut self.zugzwang_control,
+        ]
+    }
+
+    /// Read the weights from a JSON file
+    pub fn load(path: &str) -> Result<EvalParams, String> {
+        let params: EvalParams = json::load(path, "weights")?;
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Check that no weight alone takes a heuristic score up to a forced result
+    /// Each weight is bounded by the most it can be counted: stones over every line, one threat per
+    /// line, one stacked pair, one threat per cell and one side in control of the endgame
+    pub fn validate(&self) -> Result<(), String> {
+        let weights = [
+            ("line_stone", self.line_stone, 180),
+            ("threat", self.threat, 37),
+            ("stacked_threats", self.stacked_threats, 1),
+            ("good_threat", self.good_threat, 42),
+            ("other_threat", self.other_threat, 42),
+            ("zugzwang_control", self.zugzwang_control, 1),
+        ];
+        for (name, weight, count) in weights {
+            let max = MAX_HEURISTIC / count;
+            if !(-max..=max).contains(&weight) {
+                return Err(format!("Evaluation weight {} must be between {} and {}", name, -max, max));
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the weights to a JSON file, in the format of `load`
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        json::save(self, path, true)
+    }
+}
 
 /// Evaluate the board and return a score
-/// If the player is winning, the score is positive +100
-/// If the opponent is winning, the score is negative -100
-/// If no one is winning, the score is 0
-pub fn evaluate_board(board: &Connect4) -> i32 {
+/// If the player is winning, the score is positive, `WIN_SCORE` minus the plies played
+/// If the opponent is winning, the score is negative, so that a later loss scores higher
+/// If no one is winning, the score is a heuristic estimate, kept below the scores of forced results
+pub fn evaluate_board(board: &Connect4, params: &EvalParams) -> i32 {
     let turn_multi = match board.get_turn() {
         crate::Player::Red => 1,
         crate::Player::Yellow => -1,
@@ -35,15 +129,77 @@ pub fn evaluate_board(board: &Connect4) -> i32 {
     let winner = board.is_someone_winning();
     if winner.is_some() {
         return match winner.unwrap() {
-            crate::Player::Red => 100 * turn_multi,
-            crate::Player::Yellow => -100 * turn_multi,
+            crate::Player::Red => turn_multi * (WIN_SCORE - board.get_moves().len() as i32),
+            crate::Player::Yellow => -turn_multi * (WIN_SCORE - board.get_moves().len() as i32),
         };
     } else {
-        return better_evaluate(board);
+        let position = Position::from_game(board);
+        if let Some(score) = forced_result(&position) {
+            return score;
+        }
+        return (better_evaluate(board, params) + threat_evaluate(&position, params)).clamp(-MAX_HEURISTIC, MAX_HEURISTIC);
+    }
+}
+
+/// Exact score when a win can't be stopped next move: the player to move has a playable threat,
+/// or the opponent has two of them
+fn forced_result(position: &Position) -> Option<i32> {
+    let playable = position.possible();
+    if position.winning_position() & playable != 0 {
+        return Some(WIN_SCORE - position.moves - 1);
+    }
+    if (position.opponent_winning_position() & playable).count_ones() >= 2 {
+        return Some(-(WIN_SCORE - position.moves - 2));
+    }
+    None
+}
+
+/// Zugzwang aware evaluation of the threats, for the player to move
+/// Once the board fills up, red (moving first) gets the odd rows and yellow the even ones, so a
+/// threat only matters on its owner's rows and when no threat of the opponent sits below it
+fn threat_evaluate(position: &Position, params: &EvalParams) -> i32 {
+    let red_to_move = position.moves % 2 == 0;
+    let (own, opponent) = (position.winning_position(), position.opponent_winning_position());
+    let (red, yellow) = if red_to_move { (own, opponent) } else { (opponent, own) };
+
+    let red_good = unblocked_threats(red, yellow) & ODD_ROWS;
+    let yellow_good = unblocked_threats(yellow, red) & EVEN_ROWS;
+    let threat_score = |threats: u64, good: u64| {
+        params.good_threat * good.count_ones() as i32 + params.other_threat * (threats & !good).count_ones() as i32
+    };
+    let mut score = threat_score(red, red_good) - threat_score(yellow, yellow_good);
+
+    // An odd threat of red beats an even threat of yellow in another column
+    if red_good != 0 {
+        score += params.zugzwang_control;
+    } else if yellow_good != 0 {
+        score -= params.zugzwang_control;
+    }
+
+    if red_to_move {
+        score
+    } else {
+        -score
     }
 }
 
-fn better_evaluate(board: &Connect4) -> i32 {
+/// Threats with no threat of `other` below them in their column, a shared cell counting for both
+fn unblocked_threats(threats: u64, other: u64) -> u64 {
+    (0..7)
+        .map(|col| {
+            let column = threats & column_mask(col);
+            let blockers = other & column_mask(col);
+            if blockers == 0 {
+                column
+            } else {
+                let lowest = blockers & blockers.wrapping_neg();
+                column & ((lowest << 1) - 1)
+            }
+        })
+        .fold(0, |all, column| all | column)
+}
+
+fn better_evaluate(board: &Connect4, params: &EvalParams) -> i32 {
     let mut score = 0;
     let mut count = 0;
     let turn = board.get_turn();
@@ -62,7 +218,7 @@ fn better_evaluate(board: &Connect4) -> i32 {
                 break;
             }
         }
-        score += count;
+        score += count * params.line_stone;
         count = 0;
     }
 
@@ -76,7 +232,7 @@ fn better_evaluate(board: &Connect4) -> i32 {
                 break;
             }
         }
-        score += count;
+        score += count * params.line_stone;
         count = 0;
     }
 
@@ -92,7 +248,7 @@ fn better_evaluate(board: &Connect4) -> i32 {
                         break;
                     }
                 }
-                score += count;
+                score += count * params.line_stone;
                 count = 0;
             }
             if i + 3 < board.get_size().height && j >= 3 {
@@ -104,7 +260,7 @@ fn better_evaluate(board: &Connect4) -> i32 {
                         break;
                     }
                 }
-                score += count;
+                score += count * params.line_stone;
                 count = 0;
             }
         }
@@ -112,13 +268,13 @@ fn better_evaluate(board: &Connect4) -> i32 {
 
     let (threat, threat_list) = count_threat(board);
     // threat worth 10 points
-    score += threat * 10;
+    score += threat as i32 * params.threat;
 
     if is_threat_one_above_another(threat_list) {
-        score += 20;
+        score += params.stacked_threats;
     }
 
-    score as i32
+    score
 }
 
 fn is_threat_one_above_another(threat_list: Vec<Threat>) -> bool {
@@ -134,44 +290,366 @@ fn is_threat_one_above_another(threat_list: Vec<Threat>) -> bool {
     false
 }
 
-pub fn evaluate_position(board: &mut Connect4, max_depth: i32) -> i32 {
-    let mut position_history: TranspositionTable = HashMap::new();
-    let score = alpha_beta_pruning(board, max_depth, -10000, 10000, &mut position_history, &mut None);
-    score
+/// Center columns first, the order used when nothing better is known
+const COLUMN_ORDER: [u32; 7] = [3, 2, 4, 1, 5, 0, 6];
+
+/// How the alpha-beta search orders the moves of a position
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MoveOrdering {
+    /// Center columns first
+    Static,
+    /// Table move, immediate wins, forced blocks, killer moves, then by history
+    Dynamic,
 }
 
-pub fn find_best_move(
+impl std::str::FromStr for MoveOrdering {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "static" => Ok(MoveOrdering::Static),
+            "dynamic" => Ok(MoveOrdering::Dynamic),
+            _ => Err(format!("Unknown move ordering: {}", s)),
+        }
+    }
+}
+
+/// How the search windows are chosen
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchAlgorithm {
+    /// Every root move searched with the full window
+    AlphaBeta,
+    /// Principal variation search: the first move gets the full window, the others a null window
+    /// and a re-search when they beat it. The root window is centered on the previous iteration's score.
+    Pvs,
+}
+
+impl std::str::FromStr for SearchAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alphabeta" => Ok(SearchAlgorithm::AlphaBeta),
+            "pvs" => Ok(SearchAlgorithm::Pvs),
+            _ => Err(format!("Unknown search algorithm: {}", s)),
+        }
+    }
+}
+
+/// Stops a running search from another thread
+/// The search then returns its last completed iteration, the flag is cleared once it has returned
+#[derive(Clone, Debug, Default)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+    pub fn new() -> StopHandle {
+        StopHandle::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn clear(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Half width of the aspiration window around the previous iteration's score
+const ASPIRATION_WINDOW: i32 = 25;
+
+/// Settings of the alpha-beta search
+#[derive(Clone, Debug)]
+pub struct SearchOptions {
+    pub algorithm: SearchAlgorithm,
+    pub ordering: MoveOrdering,
+    /// Search threads, sharing the transposition table
+    pub threads: usize,
+    pub eval: EvalParams,
+    /// Consulted before searching
+    pub book: Option<Arc<OpeningBook>>,
+    /// Book moves scoring up to this much below the best one are played too
+    pub book_margin: i32,
+    /// Solved positions, consulted at every node and given the wins and losses proven at the root
+    pub database: Option<Arc<PositionDb>>,
+    /// Shared by the clones of these options
+    pub stop: StopHandle,
+    /// Print the value of each root move and the time spent in each part of the search
+    pub verbose: bool,
+    /// Last completed iteration of an interrupted search of the same position, deepening goes on from there
+    pub resume: Option<SearchResult>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            algorithm: SearchAlgorithm::Pvs,
+            ordering: MoveOrdering::Dynamic,
+            threads: 1,
+            eval: EvalParams::default(),
+            book: None,
+            book_margin: 0,
+            database: None,
+            stop: StopHandle::new(),
+            verbose: false,
+            resume: None,
+        }
+    }
+}
+
+/// State of one search thread
+struct SearchContext<'a> {
+    table: &'a TranspositionTable,
+    /// Set once the main search is done, to stop the helpers
+    stop: &'a AtomicBool,
+    /// Set from outside the search
+    cancel: &'a AtomicBool,
+    database: Option<&'a PositionDb>,
+    algorithm: SearchAlgorithm,
+    verbose: bool,
+    ordering: MoveOrdering,
+    eval: EvalParams,
+    resume: Option<SearchResult>,
+    /// Two moves per ply that recently caused a cutoff
+    killers: [[Option<u32>; 2]; 43],
+    /// Cutoffs caused by each column, per player
+    history: [[u32; 7]; 2],
+    nodes: u64,
+}
+
+impl<'a> SearchContext<'a> {
+    fn new(table: &'a TranspositionTable, stop: &'a AtomicBool, options: &'a SearchOptions) -> SearchContext<'a> {
+        SearchContext {
+            table,
+            stop,
+            cancel: &options.stop.0,
+            database: options.database.as_deref(),
+            algorithm: options.algorithm,
+            verbose: options.verbose,
+            ordering: options.ordering,
+            eval: options.eval,
+            resume: options.resume,
+            killers: [[None; 2]; 43],
+            history: [[0; 7]; 2],
+            nodes: 0,
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed) || self.cancel.load(Ordering::Relaxed)
+    }
+
+    fn order_moves(&self, board: &Connect4, table_move: Option<u32>) -> Vec<u32> {
+        if self.ordering == MoveOrdering::Static {
+            return COLUMN_ORDER.to_vec();
+        }
+        let position = Position::from_game(board);
+        let threatened = position.threatened_columns();
+        let killers = self.killers[board.get_moves().len()];
+        let history = &self.history[*board.get_turn() as usize];
+
+        let mut moves: Vec<u32> = COLUMN_ORDER.into_iter().filter(|&col| position.can_play(col)).collect();
+        // Stable sort, equal moves keep the center-first order
+        moves.sort_by_key(|&col| {
+            let priority = if Some(col) == table_move {
+                u32::MAX
+            } else if position.is_winning_move(col) {
+                u32::MAX - 1
+            } else if threatened.contains(&col) {
+                u32::MAX - 2
+            } else if Some(col) == killers[0] {
+                u32::MAX - 3
+            } else if Some(col) == killers[1] {
+                u32::MAX - 4
+            } else {
+                history[col as usize].min(u32::MAX - 5)
+            };
+            Reverse(priority)
+        });
+        moves
+    }
+
+    fn record_cutoff(&mut self, board: &Connect4, col: u32, depth: i32) {
+        let killers = &mut self.killers[board.get_moves().len()];
+        if killers[0] != Some(col) {
+            killers[1] = killers[0];
+            killers[0] = Some(col);
+        }
+        let history = &mut self.history[*board.get_turn() as usize][col as usize];
+        *history = history.saturating_add((depth * depth) as u32);
+    }
+}
+
+pub fn evaluate_position(board: &mut Connect4, max_depth: i32, table: &TranspositionTable) -> i32 {
+    table.new_search();
+    let stop = AtomicBool::new(false);
+    let options = SearchOptions::default();
+    let mut ctx = SearchContext::new(table, &stop, &options);
+    alpha_beta_pruning(board, max_depth, -10000, 10000, &mut ctx, &mut None)
+}
+
+/// Score of every legal move for the player to move, searched to `depth`, center columns first
+pub fn score_moves(board: &mut Connect4, depth: i32, table: &TranspositionTable, eval: &EvalParams) -> Vec<(u32, i32)> {
+    table.new_search();
+    let stop = AtomicBool::new(false);
+    let options = SearchOptions { eval: *eval, ..SearchOptions::default() };
+    let mut ctx = SearchContext::new(table, &stop, &options);
+    let mut scores = Vec::new();
+    for col in COLUMN_ORDER {
+        if board.play(col) {
+            let score = -alpha_beta_pruning(board, depth - 1, -10000, 10000, &mut ctx, &mut None);
+            board.undo().unwrap();
+            scores.push((col, score));
+        }
+    }
+    scores
+}
+
+/// Find the best move for the player to move
+/// Iterative deepening stops at `max_depth`, or earlier once `time_limit` has elapsed
+/// The time limit is only checked between iterations, so a deep iteration can overrun it
+/// `rng` drives the occasional random deviation, a seeded one replays the same decisions
+/// `table` keeps what was learned for the next searches, entries of older searches are replaced first
+/// A position found in the book is not searched, its book move is played
+/// Lazy SMP: with more than one thread, helpers search the same position with other move orders and
+/// fill the shared table, which lets the main search reach the same depth sooner
+/// The helpers stop as soon as the main search is done, only its result is used
+/// `options.stop` interrupts the search from another thread
+pub fn search_with_options(
     board: &mut Connect4,
     max_depth: i32,
-    bench: &mut Option<&mut BenchmarkTimer>,
-) -> u32 {
-    let mut best_move = 0;
-    let mut position_history: TranspositionTable = HashMap::new();
+    time_limit: Option<Duration>,
+    rng: &mut dyn RngCore,
+    table: &TranspositionTable,
+    options: &SearchOptions,
+) -> SearchResult {
+    let result = known_move(board, options, rng)
+        .unwrap_or_else(|| parallel_search(board, max_depth, time_limit, rng, table, options));
+    options.stop.clear();
+    result
+}
 
-    // Column ordering heuristic - prioritize center columns
-    let column_order = [3, 2, 4, 1, 5, 0, 6];
-    let mut death_moves = vec![];
+/// Move from the book or the solved positions, if the position is in one of them
+fn known_move(board: &Connect4, options: &SearchOptions, rng: &mut dyn RngCore) -> Option<SearchResult> {
+    if let Some(mv) = options.book.as_ref().and_then(|book| book.pick(board, options.book_margin, rng)) {
+        return Some(SearchResult {
+            best_move: mv.col,
+            score: mv.score,
+            depth: 0,
+            nodes: 0,
+        });
+    }
+    let moves = board.get_moves().len() as i32;
+    let solved = options.database.as_ref().and_then(|database| database.lookup(board))?;
+    solved.best_move.map(|best_move| SearchResult {
+        best_move,
+        score: solver::search_score(solved.score, moves),
+        depth: solver::plies_to_end(solved.score, moves),
+        nodes: 0,
+    })
+}
 
-    // Implement iterative deepening - start with low depth and progressively increase
-    for depth in 1..=max_depth {
-        let mut best_value = -10000;
-        let mut local_best_move = 0;
-        if let Some(bench_timer) = bench.as_mut() {
-            bench_timer.start("iterative_deepening");
+fn parallel_search(
+    board: &mut Connect4,
+    max_depth: i32,
+    time_limit: Option<Duration>,
+    rng: &mut dyn RngCore,
+    table: &TranspositionTable,
+    options: &SearchOptions,
+) -> SearchResult {
+    table.new_search();
+    let stop = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let helpers: Vec<_> = (1..options.threads)
+            .map(|helper| {
+                let mut board = board.clone();
+                let stop = &stop;
+                scope.spawn(move || helper_search(&mut board, max_depth, table, stop, options, helper))
+            })
+            .collect();
+        let mut ctx = SearchContext::new(table, &stop, options);
+        let mut bench = BenchmarkTimer::new();
+        bench.start("search");
+        let mut timer = options.verbose.then_some(&mut bench);
+        let mut result = deepen(board, max_depth, time_limit, rng, &mut ctx, &mut timer);
+        if options.verbose {
+            bench.stop("search");
+            bench.print();
         }
+        stop.store(true, Ordering::Relaxed);
+        result.nodes += helpers.into_iter().map(|helper| helper.join().unwrap()).sum::<u64>();
+        result
+    })
+}
+
+/// Iterative deepening of a helper thread, half of them one depth ahead of the main search
+/// Returns the nodes searched
+fn helper_search(
+    board: &mut Connect4,
+    max_depth: i32,
+    table: &TranspositionTable,
+    stop: &AtomicBool,
+    options: &SearchOptions,
+    helper: usize,
+) -> u64 {
+    let mut ctx = SearchContext::new(table, stop, options);
+    let mut column_order = COLUMN_ORDER;
+    column_order.rotate_left(helper % 7);
+    for depth in (1 + helper as i32 % 2)..=max_depth {
         for &i in &column_order {
             if board.play(i) {
-                if let Some(bench_timer) = bench.as_mut() {
-                    bench_timer.start("alpha_beta_pruning");
-                }
-                let value =
-                    -alpha_beta_pruning(board, depth - 1, -10000, 10000, &mut position_history, bench);
-                if let Some(bench_timer) = bench.as_mut() {
-                    bench_timer.stop("alpha_beta_pruning");
-                }
+                alpha_beta_pruning(board, depth - 1, -10000, 10000, &mut ctx, &mut None);
                 board.undo().unwrap();
+            }
+            if ctx.stopped() {
+                return ctx.nodes;
+            }
+        }
+    }
+    ctx.nodes
+}
+
+fn deepen(
+    board: &mut Connect4,
+    max_depth: i32,
+    time_limit: Option<Duration>,
+    rng: &mut dyn RngCore,
+    ctx: &mut SearchContext,
+    bench: &mut Option<&mut BenchmarkTimer>,
+) -> SearchResult {
+    let start = Instant::now();
+    let (mut best_move, mut best_score, mut completed_depth) =
+        ctx.resume.map_or((0, 0, 0), |resume| (resume.best_move, resume.score, resume.depth));
+    let mut death_moves = vec![];
 
-                if depth == max_depth {
+    // Implement iterative deepening - start with low depth and progressively increase
+    for depth in completed_depth + 1..=max_depth {
+        if depth > 1 && (ctx.stopped() || time_limit.is_some_and(|limit| start.elapsed() >= limit)) {
+            break;
+        }
+        if let Some(bench_timer) = bench.as_mut() {
+            bench_timer.start("iterative_deepening");
+        }
+        // The previous iteration's best move is tried first
+        let table_move = table_move(ctx.table, board);
+        let moves = ctx.order_moves(board, table_move);
+        let (mut alpha, mut beta) = match ctx.algorithm {
+            SearchAlgorithm::Pvs if depth > 1 && !is_decisive(best_score) => {
+                (best_score - ASPIRATION_WINDOW, best_score + ASPIRATION_WINDOW)
+            }
+            _ => (-10000, 10000),
+        };
+        let mut best_value = -10000;
+        let mut local_best_move = 0;
+        loop {
+            for (i, value) in search_root(board, depth, alpha, beta, &moves, ctx, bench) {
+                if ctx.verbose && depth == max_depth {
                     println!("Move {} has value {}", i, value);
                 }
 
@@ -180,28 +658,57 @@ pub fn find_best_move(
                     local_best_move = i;
                 }
 
-                if value == -100 {
+                if value <= -WIN_THRESHOLD {
                     // If we find a losing move, add it to the death_moves list
                     death_moves.push(i);
                 }
             }
+            if ctx.stopped() {
+                break;
+            }
+            // Outside the aspiration window the score is only a bound, search again with the full window
+            if (best_value <= alpha || best_value >= beta) && (alpha, beta) != (-10000, 10000) {
+                (alpha, beta) = (-10000, 10000);
+                best_value = -10000;
+                continue;
+            }
+            break;
         }
         if let Some(bench_timer) = bench.as_mut() {
             bench_timer.stop("iterative_deepening");
         }
+        // An interrupted iteration is dropped, the first one always completes as its nodes are leaves
+        if depth > 1 && ctx.stopped() {
+            break;
+        }
 
         best_move = local_best_move;
+        best_score = best_value;
+        completed_depth = depth;
+        let (key, mirrored) = board.canonical_hash();
+        ctx.table.store(key, best_value, depth, NodeType::Exact, Some(oriented(board, best_move, mirrored)));
 
         // If we found a winning move, no need to search deeper
-        if best_value >= 90 {
-            println!("Found winning move at depth {}", depth);
+        if best_value >= WIN_THRESHOLD {
+            if ctx.verbose {
+                println!("Found winning move at depth {}", depth);
+            }
             break;
         }
     }
 
+    // A win or loss found within the searched depth is the exact value of the position
+    let moves = board.get_moves().len();
+    if let (Some(database), Some(plies)) = (ctx.database, plies_to_win(best_score, moves)) {
+        if plies.abs() <= completed_depth {
+            let score = solver::solver_score(best_score);
+            database.record(board, SolvedPosition { score, best_move: Some(best_move) });
+        }
+    }
+
     // Add a small amount of randomness to avoid predictable play
-    if rand::thread_rng().gen_bool(0.05) && best_move != 3 {
-        let random_column = column_order[rand::thread_rng().gen_range(0..3)];
+    if rng.gen_bool(0.05) && best_move != 3 {
+        let random_column = COLUMN_ORDER[rng.gen_range(0..3)];
 
         // check if random column is not a kill move
         if !death_moves.contains(&random_column) {
@@ -213,7 +720,73 @@ pub fn find_best_move(
         }
     }
 
-    best_move
+    SearchResult {
+        best_move,
+        score: best_score,
+        depth: completed_depth,
+        nodes: ctx.nodes,
+    }
+}
+
+/// Search the root moves within `alpha..beta`, returns the value of each move searched
+/// With PVS only the best move's value is exact, the others are bounds
+fn search_root(
+    board: &mut Connect4,
+    depth: i32,
+    mut alpha: i32,
+    beta: i32,
+    moves: &[u32],
+    ctx: &mut SearchContext,
+    bench: &mut Option<&mut BenchmarkTimer>,
+) -> Vec<(u32, i32)> {
+    let mut values = Vec::new();
+    let mut first = true;
+    for &i in moves {
+        if board.play(i) {
+            if let Some(bench_timer) = bench.as_mut() {
+                bench_timer.start("alpha_beta_pruning");
+            }
+            let value = match ctx.algorithm {
+                SearchAlgorithm::AlphaBeta => -alpha_beta_pruning(board, depth - 1, -10000, 10000, ctx, bench),
+                SearchAlgorithm::Pvs if first => -alpha_beta_pruning(board, depth - 1, -beta, -alpha, ctx, bench),
+                SearchAlgorithm::Pvs => {
+                    let value = -alpha_beta_pruning(board, depth - 1, -alpha - 1, -alpha, ctx, bench);
+                    if value > alpha && value < beta {
+                        -alpha_beta_pruning(board, depth - 1, -beta, -alpha, ctx, bench)
+                    } else {
+                        value
+                    }
+                }
+            };
+            if let Some(bench_timer) = bench.as_mut() {
+                bench_timer.stop("alpha_beta_pruning");
+            }
+            board.undo().unwrap();
+            first = false;
+            values.push((i, value));
+
+            alpha = alpha.max(value);
+            if value >= beta {
+                break;
+            }
+        }
+    }
+    values
+}
+
+/// Best move the table holds for `board`
+pub(crate) fn table_move(table: &TranspositionTable, board: &Connect4) -> Option<u32> {
+    let (key, mirrored) = board.canonical_hash();
+    table.probe(key).and_then(|entry| entry.best_move).map(|col| oriented(board, col, mirrored))
+}
+
+/// Move of `board` as stored under its canonical hash, or the other way around
+fn oriented(board: &Connect4, col: u32, mirrored: bool) -> u32 {
+    if mirrored {
+        board.mirror_move(col)
+    } else {
+        col
+    }
 }
 
 fn alpha_beta_pruning(
@@ -221,9 +794,10 @@ fn alpha_beta_pruning(
     depth: i32,
     mut alpha: i32,
     mut beta: i32,
-    position_history: &mut TranspositionTable,
+    ctx: &mut SearchContext,
     bench: &mut Option<&mut BenchmarkTimer>,
 ) -> i32 {
+    ctx.nodes += 1;
     // Add early return for draw condition
     if board.is_draw() {
         return 0;
@@ -233,11 +807,17 @@ fn alpha_beta_pruning(
     if let Some(bench_timer) = bench.as_mut() {
         bench_timer.start("transposition_table");
     }
-    let board_hash = board.get_hash();
+    // A position and its mirror image share their entries
+    let (board_hash, mirrored) = board.canonical_hash();
     if let Some(bench_timer) = bench.as_mut() {
         bench_timer.stop("transposition_table");
     }
-    if let Some(entry) = position_history.get(&board_hash) {
+    if let Some(solved) = ctx.database.and_then(|database| database.get(board_hash)) {
+        return solver::search_score(solved.score, board.get_moves().len() as i32);
+    }
+    let mut table_move = None;
+    if let Some(entry) = ctx.table.probe(board_hash) {
+        table_move = entry.best_move.map(|col| oriented(board, col, mirrored));
         if entry.depth >= depth {
             match entry.node_type {
                 NodeType::Exact => return entry.score,
@@ -255,35 +835,43 @@ fn alpha_beta_pruning(
     if let Some(bench_timer) = bench.as_mut() {
         bench_timer.start("evaluate_board");
     }
-    let score = evaluate_board(board);
+    let score = evaluate_board(board, &ctx.eval);
     if let Some(bench_timer) = bench.as_mut() {
         bench_timer.stop("evaluate_board");
     }
-    if depth == 0 || score.abs() == 100 {
+    if depth == 0 || is_decisive(score) {
         // Store the result in the transposition table
-        position_history.insert(
-            board_hash,
-            TranspositionEntry {
-                score,
-                depth,
-                node_type: NodeType::Exact,
-            },
-        );
+        ctx.table.store(board_hash, score, depth, NodeType::Exact, None);
         return score;
     }
 
-    // Add column ordering heuristic - prioritize center columns
-    let column_order = [3, 2, 4, 1, 5, 0, 6]; // Center-first ordering
-
-    let mut best_score = -1000;
+    let mut best_score = -10000;
+    let mut best_move = None;
     let original_alpha = alpha;
 
-    for &i in &column_order {
+    for i in ctx.order_moves(board, table_move) {
         if board.play(i) {
-            let value = -alpha_beta_pruning(board, depth - 1, -beta, -alpha, position_history, bench);
+            let value = if ctx.algorithm == SearchAlgorithm::Pvs && best_move.is_some() {
+                // Only prove the move is no better than the best one, search it fully if it is
+                let value = -alpha_beta_pruning(board, depth - 1, -alpha - 1, -alpha, ctx, bench);
+                if value > alpha && value < beta {
+                    -alpha_beta_pruning(board, depth - 1, -beta, -alpha, ctx, bench)
+                } else {
+                    value
+                }
+            } else {
+                -alpha_beta_pruning(board, depth - 1, -beta, -alpha, ctx, bench)
+            };
             board.undo().unwrap();
+            // A stopped search returns garbage, keep it out of the table
+            if ctx.stopped() {
+                return 0;
+            }
 
-            best_score = best_score.max(value);
+            if value > best_score {
+                best_score = value;
+                best_move = Some(i);
+            }
 
             if best_score > alpha {
                 alpha = best_score;
@@ -291,14 +879,9 @@ fn alpha_beta_pruning(
 
             if alpha >= beta {
                 // Store a lower bound in the transposition table
-                position_history.insert(
-                    board_hash,
-                    TranspositionEntry {
-                        score: best_score,
-                        depth,
-                        node_type: NodeType::LowerBound,
-                    },
-                );
+                ctx.record_cutoff(board, i, depth);
+                let best_move = best_move.map(|col| oriented(board, col, mirrored));
+                ctx.table.store(board_hash, best_score, depth, NodeType::LowerBound, best_move);
                 return best_score;
             }
         }
@@ -311,14 +894,8 @@ fn alpha_beta_pruning(
         NodeType::Exact
     };
 
-    position_history.insert(
-        board_hash,
-        TranspositionEntry {
-            score: best_score,
-            depth,
-            node_type,
-        },
-    );
+    let best_move = best_move.map(|col| oriented(board, col, mirrored));
+    ctx.table.store(board_hash, best_score, depth, node_type, best_move);
 
     best_score
 }
@@ -468,6 +1045,11 @@ fn count_threat(board: &Connect4) -> (u32, Vec<Threat>) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::book::BookMove;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::engine::EngineConfig;
+    use crate::tournament::{run_tournament, Format, TournamentConfig};
 
     #[test]
     fn test_evaluate_board_winning() {
@@ -480,7 +1062,7 @@ mod tests {
         game.play(2);
         game.play(3);
         game.play(0);
-        assert_eq!(evaluate_board(&game), 100);
+        assert_eq!(evaluate_board(&game, &EvalParams::default()), WIN_SCORE - 8);
     }
 
     #[test]
@@ -497,6 +1079,166 @@ mod tests {
         game.play(3); // Yellow
         game.play(4); // Red
         game.play(3); // Yellow
-        assert_eq!(evaluate_board(&game), -100);
+        assert_eq!(evaluate_board(&game, &EvalParams::default()), -(WIN_SCORE - 10));
+    }
+
+    #[test]
+    fn test_prefers_faster_win() {
+        // Red wins at once on top of column 6, or two plies later by opening three on the bottom row
+        let mut game = Connect4::new();
+        for col in [2, 2, 3, 3, 6, 0, 6, 0, 6, 2] {
+            game.play(col);
+        }
+        let scores = score_moves(&mut game, 3, &TranspositionTable::new(1), &EvalParams::default());
+        let best = scores.iter().copied().max_by_key(|&(_, score)| score).unwrap();
+        assert_eq!(best, (6, WIN_SCORE - 11));
+        assert!(scores.contains(&(4, WIN_SCORE - 13)));
+    }
+
+    #[test]
+    fn test_eval_params() {
+        let params: EvalParams = serde_json::from_str(r#"{ "threat": 50 }"#).unwrap();
+        assert_eq!(params.threat, 50);
+        assert_eq!(params.good_threat, EvalParams::default().good_threat);
+
+        // Red has two stones on the bottom row, weighting lines alone doubles the score
+        let mut game = Connect4::new();
+        for col in [2, 6, 3, 6] {
+            game.play(col);
+        }
+        let lines_only = EvalParams { line_stone: 1, threat: 0, stacked_threats: 0, good_threat: 0, other_threat: 0, zugzwang_control: 0 };
+        let double = EvalParams { line_stone: 2, ..lines_only };
+        assert_eq!(evaluate_board(&game, &double), 2 * evaluate_board(&game, &lines_only));
+
+        // Weights out of bounds are refused, and the heuristic never reads as a forced result anyway
+        assert!(EvalParams::default().validate().is_ok());
+        assert!(EvalParams { line_stone: 6, ..lines_only }.validate().is_err());
+        assert!(EvalParams { zugzwang_control: i32::MIN, ..lines_only }.validate().is_err());
+        let huge = EvalParams { line_stone: 100_000, ..lines_only };
+        assert_eq!(evaluate_board(&game, &huge), MAX_HEURISTIC);
+    }
+
+    #[test]
+    fn test_search_plays_book_moves() {
+        // A book move no search would pick, on the edge
+        let mut book = OpeningBook::default();
+        book.positions.insert(Connect4::new().canonical_hash().0, vec![BookMove { col: 0, score: 0 }]);
+        let table = TranspositionTable::new(1);
+        let mut rng = StdRng::seed_from_u64(0);
+        let with_book = SearchOptions { book: Some(Arc::new(book)), ..SearchOptions::default() };
+        let mut search = |board: &mut Connect4, options: &SearchOptions| {
+            search_with_options(board, 4, None, &mut rng, &table, options).best_move
+        };
+        let mut board = Connect4::new();
+        assert_eq!(search(&mut board, &with_book), 0);
+        assert_ne!(search(&mut board, &SearchOptions::default()), 0);
+        // Out of the book, the search plays
+        board.play(3);
+        assert_ne!(search(&mut board, &with_book), 0);
+    }
+
+    #[test]
+    fn test_search_resumes_from_completed_iteration() {
+        let table = TranspositionTable::new(1);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut board = Connect4::new();
+        board.play(3);
+        let first = search_with_options(&mut board, 4, None, &mut rng, &table, &SearchOptions::default());
+        assert_eq!(first.depth, 4);
+        let resume = SearchOptions { resume: Some(first), ..SearchOptions::default() };
+        // Nothing left to search at the same depth, deeper ones start right after it
+        assert_eq!(search_with_options(&mut board, 4, None, &mut rng, &table, &resume).nodes, 0);
+        assert_eq!(search_with_options(&mut board, 6, None, &mut rng, &table, &resume).depth, 6);
+    }
+
+    #[test]
+    fn test_mirror_shares_table_entries() {
+        let mut board = Connect4::new();
+        for col in [1, 2, 1] {
+            board.play(col);
+        }
+        let table = TranspositionTable::new(1);
+        evaluate_position(&mut board, 6, &table);
+        let best = table_move(&table, &board).unwrap();
+        assert_eq!(table_move(&table, &board.mirror()), Some(6 - best));
+    }
+
+    fn position(moves: &[u32]) -> Position {
+        let mut game = Connect4::new();
+        for &col in moves {
+            game.play(col);
+        }
+        Position::from_game(&game)
+    }
+
+    fn cell(col: u32, row: u32) -> u64 {
+        1 << (col * 7 + row)
+    }
+
+    #[test]
+    fn test_forced_result() {
+        // Red can connect four in column 0
+        assert_eq!(forced_result(&position(&[0, 1, 0, 1, 0, 1])), Some(WIN_SCORE - 7));
+        // A single threat of red can still be blocked
+        assert_eq!(forced_result(&position(&[0, 1, 0, 1, 0])), None);
+        // Red's open three threatens both ends of the bottom row
+        assert_eq!(forced_result(&position(&[2, 2, 3, 3, 4])), Some(-(WIN_SCORE - 7)));
+    }
+
+    #[test]
+    fn test_unblocked_threats() {
+        let threats = cell(2, 2) | cell(2, 4) | cell(5, 4);
+        assert_eq!(unblocked_threats(threats, cell(2, 3)), cell(2, 2) | cell(5, 4));
+        // A cell both players threaten counts for both
+        assert_eq!(unblocked_threats(cell(2, 3), cell(2, 3)), cell(2, 3));
+        assert_eq!(unblocked_threats(threats, 0), threats);
+    }
+
+    #[test]
+    fn test_threat_evaluate() {
+        let params = EvalParams::default();
+        // Red threatens the third row of column 3, an odd row, and controls the endgame
+        let odd = position(&[1, 0, 0, 2, 2, 1, 0, 5, 1, 6, 2]);
+        assert_eq!(odd.opponent_winning_position(), cell(3, 2));
+        assert_eq!(threat_evaluate(&odd, &params), -(params.good_threat + params.zugzwang_control));
+
+        // The same threat on the second row, an even one, is worth less and controls nothing
+        let even = position(&[2, 0, 0, 1, 1, 6, 2]);
+        assert_eq!(even.opponent_winning_position(), cell(3, 1));
+        assert_eq!(threat_evaluate(&even, &params), -params.other_threat);
+
+        // Yellow's even threat right below red's odd one takes the control over
+        let blocked = position(&[1, 0, 0, 2, 2, 1, 0, 5, 1, 6, 2, 5, 4, 6, 5, 4, 4]);
+        assert_eq!(blocked.winning_position(), cell(3, 1));
+        assert_eq!(blocked.opponent_winning_position(), cell(3, 2));
+        assert_eq!(
+            threat_evaluate(&blocked, &params),
+            params.good_threat + params.zugzwang_control - params.other_threat
+        );
+    }
+
+    #[test]
+    fn test_threats_beat_old_eval() {
+        // The evaluation before the threat rules, also in doc/eval_no_threats.json
+        let old = EvalParams { good_threat: 0, other_threat: 0, zugzwang_control: 0, ..EvalParams::default() };
+        let engine = |eval| EngineConfig { depth: Some(3), eval: Some(eval), ..EngineConfig::new("minimax") };
+        let config = TournamentConfig {
+            participants: vec![engine(EvalParams::default()), engine(old)],
+            format: Format::Gauntlet,
+            opening_plies: 2,
+            threads: 1,
+            sprt: None,
+            seed: 1,
+        };
+        let result = run_tournament(&config)[0].result;
+        let estimate = result.elo().unwrap();
+        assert!(estimate.elo - estimate.error > 0.0, "{} Elo {:.0} +/- {:.0}", result, estimate.elo, estimate.error);
+    }
+
+    #[test]
+    fn test_plies_to_win() {
+        assert_eq!(plies_to_win(WIN_SCORE - 9, 4), Some(5));
+        assert_eq!(plies_to_win(-(WIN_SCORE - 12), 4), Some(-8));
+        assert_eq!(plies_to_win(35, 4), None);
     }
 }
@@ -0,0 +1,130 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::Serialize;
+
+use std::time::Instant;
+
+use crate::engine::{EvalParams, SearchLimits};
+use crate::evaluator::score_moves;
+use crate::solver::Position;
+use crate::transposition::TranspositionTable;
+use crate::Connect4;
+
+/// Search depth of a hint when no budget is given
+pub const DEFAULT_HINT_DEPTH: i32 = 8;
+/// Transposition table of a hint's search, a few plies don't need more
+const HINT_HASH_MB: usize = 4;
+
+/// Why a column is suggested
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HintReason {
+    /// Connects four right away
+    WinsNow,
+    /// Takes the only cell where the opponent would connect four
+    BlocksWin,
+    /// Leaves two cells connecting four, the opponent can only block one
+    DoubleThreat,
+    /// Nothing forced, the best move of a full-strength search
+    BestBySearch,
+}
+
+impl Display for HintReason {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            HintReason::WinsNow => write!(f, "wins now"),
+            HintReason::BlocksWin => write!(f, "blocks a win"),
+            HintReason::DoubleThreat => write!(f, "creates a double threat"),
+            HintReason::BestBySearch => write!(f, "best by search"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Hint {
+    pub column: u32,
+    pub reason: HintReason,
+    /// Search score of the move for the player to move, unset when the move is forced
+    pub score: Option<i32>,
+}
+
+/// Suggest a move for the player to move, searching when no move is forced
+/// The search scores every move without any of the engines' randomness, so a position always gets the
+/// same hint for the same depth, the time limit only stopping it between depths
+/// None when the game is over
+pub fn hint(game: &Connect4, limits: &SearchLimits) -> Option<Hint> {
+    if game.is_someone_winning().is_some() || game.is_draw() {
+        return None;
+    }
+
+    let position = Position::from_game(game);
+    if let Some(&column) = position.winning_columns().first() {
+        return Some(Hint { column, reason: HintReason::WinsNow, score: None });
+    }
+    // Against several threats the game is lost anyway, let the search pick
+    if let [column] = position.threatened_columns()[..] {
+        return Some(Hint { column, reason: HintReason::BlocksWin, score: None });
+    }
+
+    let (column, score) = best_move(game, limits);
+    let mut child = position;
+    child.play(column);
+    // The opponent has to move and can't stop both
+    let reason = if child.threatened_columns().len() >= 2 {
+        HintReason::DoubleThreat
+    } else {
+        HintReason::BestBySearch
+    };
+    Some(Hint { column, reason, score: Some(score) })
+}
+
+/// Best scoring move of the deepest search done, the first in center-first order on ties
+fn best_move(game: &Connect4, limits: &SearchLimits) -> (u32, i32) {
+    let start = Instant::now();
+    let table = TranspositionTable::new(HINT_HASH_MB);
+    let mut board = game.clone();
+    let mut best = None;
+    for depth in 1..=limits.depth.max(1) {
+        if best.is_some() && limits.time.is_some_and(|limit| start.elapsed() >= limit) {
+            break;
+        }
+        let scores = score_moves(&mut board, depth, &table, &EvalParams::default());
+        best = scores.into_iter().reduce(|best, mv| if mv.1 > best.1 { mv } else { best });
+    }
+    best.unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::position;
+
+    fn reason(moves: &str) -> Option<(u32, HintReason)> {
+        hint(&position(moves), &SearchLimits::depth(6)).map(|hint| (hint.column, hint.reason))
+    }
+
+    #[test]
+    fn test_hint_reasons() {
+        assert_eq!(reason("010106"), Some((0, HintReason::WinsNow)));
+        assert_eq!(reason("01010"), Some((0, HintReason::BlocksWin)));
+        // Red holds 2 and 3 on the bottom row, 1 or 4 leaves both ends open
+        let (column, why) = reason("2233").unwrap();
+        assert!(column == 1 || column == 4);
+        assert_eq!(why, HintReason::DoubleThreat);
+        assert_eq!(reason("").unwrap().1, HintReason::BestBySearch);
+        assert_eq!(reason("0101010"), None);
+    }
+
+    #[test]
+    fn test_hint_is_legal_and_repeatable() {
+        // Column 0 is full, even a search of no depth has to avoid it
+        let full = position("000000");
+        for depth in [-1, 0, 1] {
+            let column = hint(&full, &SearchLimits::depth(depth)).unwrap().column;
+            assert!(full.is_legal(column), "depth {} hints column {}", depth, column);
+        }
+        let game = position("332");
+        let first = hint(&game, &SearchLimits::depth(6));
+        assert!((0..5).all(|_| hint(&game, &SearchLimits::depth(6)) == first));
+    }
+}
@@ -0,0 +1,33 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Read a value from a JSON file, `what` names it in the errors
+pub(crate) fn load<T: DeserializeOwned>(path: &str, what: &str) -> Result<T, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Invalid {} {}: {}", what, path, e))
+}
+
+/// Write a value to a JSON file, indented when `pretty` for files meant to be read by hand
+pub(crate) fn save<T: Serialize>(value: &T, path: &str, pretty: bool) -> Result<(), String> {
+    let json = if pretty { serde_json::to_string_pretty(value) } else { serde_json::to_string(value) };
+    std::fs::write(path, json.map_err(|e| e.to_string())?).map_err(|e| format!("Cannot write {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join("connect4_test_json.json");
+        let path = path.to_str().unwrap();
+        for pretty in [false, true] {
+            save(&vec![3, 2, 4], path, pretty).unwrap();
+            assert_eq!(load::<Vec<u32>>(path, "moves"), Ok(vec![3, 2, 4]));
+        }
+        std::fs::write(path, "{").unwrap();
+        assert!(load::<Vec<u32>>(path, "moves").unwrap_err().starts_with("Invalid moves"));
+        std::fs::remove_file(path).unwrap();
+        assert!(load::<Vec<u32>>(path, "moves").unwrap_err().starts_with("Cannot read"));
+    }
+}
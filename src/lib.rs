@@ -1,11 +1,25 @@
 use std::fmt::{self, Display, Formatter};
+use engine::{derive_seed, Engine, SearchLimits};
 use player::Player;
-use rand::Rng;
-use rustbenchmarktimer::timer::BenchmarkTimer;
 
 mod player;
 mod evaluator;
+mod json;
+pub mod bench;
+pub mod book;
+pub mod database;
+pub mod engine;
+pub mod hint;
+pub mod mcts;
+pub mod puzzle;
+pub mod review;
 pub mod server;
+pub mod skill;
+pub mod solver;
+pub mod tournament;
+pub mod transposition;
+pub mod tuning;
+pub mod wdl;
 
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum CellState {
@@ -14,7 +28,7 @@ pub enum CellState {
     Yellow,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Size {
     pub width: u32,
     pub height: u32,
@@ -22,6 +36,10 @@ pub struct Size {
 
 pub type Board = [[CellState; 7]; 6];
 
+/// Seed of the Zobrist keys used by `get_hash`
+pub const ZOBRIST_SEED: u64 = 0x0C04_4EC7_F0DB_5EED;
+
+#[derive(Clone)]
 pub struct Connect4 {
     board: Board,
     size: Size,
@@ -76,6 +94,44 @@ impl Connect4 {
         false
     }
 
+    /// Whether `col` exists and is not full
+    pub fn is_legal(&self, col: u32) -> bool {
+        col < self.size.width && self.board[self.size.height as usize - 1][col as usize] == CellState::Empty
+    }
+
+    /// Columns that are not full, from left to right
+    /// Moves stay legal in a won game, check `is_someone_winning` to stop there
+    pub fn legal_moves(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.size.width).filter(|&col| self.is_legal(col))
+    }
+
+    /// Number of move sequences of `depth` plies, a won game ending its sequence early
+    /// From the empty board: 7^depth up to 6 plies, then 823536 and 5686266, checked against a plain
+    /// reimplementation in the tests
+    pub fn perft(&self, depth: u32) -> u64 {
+        let mut game = self.clone();
+        game.perft_from(depth)
+    }
+
+    fn perft_from(&mut self, depth: u32) -> u64 {
+        if depth == 0 || self.is_someone_winning().is_some() {
+            return 1;
+        }
+        let moves: Vec<u32> = self.legal_moves().collect();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .into_iter()
+            .map(|col| {
+                self.play(col);
+                let nodes = self.perft_from(depth - 1);
+                self.undo().unwrap();
+                nodes
+            })
+            .sum()
+    }
+
     pub fn undo(&mut self) -> Result<(), String> {
         if self.moves.is_empty() {
             return Err("Move list empty".into());
@@ -242,13 +298,14 @@ impl Connect4 {
         true
     }
 
-    pub fn play_minimax(&mut self, depth: i32) -> u32 {
-        let mut bench = BenchmarkTimer::new();
-        bench.start("botplay");
-        let bot_move = evaluator::find_best_move(self, depth, &mut Some(&mut bench));
+    pub fn get_moves(&self) -> &[u32] {
+        &self.moves
+    }
+
+    /// Let `engine` pick a move and play it, e.g. a seeded `engine::AlphaBetaEngine` for the minimax search
+    pub fn play_engine(&mut self, engine: &mut dyn Engine, limits: &SearchLimits) -> u32 {
+        let bot_move = engine.choose_move(self, limits).best_move;
         self.play(bot_move);
-        bench.stop("botplay");
-        bench.print();
         bot_move
     }
 
@@ -265,16 +322,47 @@ impl Connect4 {
 
 
     fn get_hash(&self) -> u64 {
+        self.zobrist_hashes().0
+    }
+
+    /// Hash shared by the position and its mirror image, the smaller of their two hashes
+    /// Also tells whether it is the mirror image's hash, moves stored under it are then mirrored too
+    pub fn canonical_hash(&self) -> (u64, bool) {
+        let (hash, mirrored_hash) = self.zobrist_hashes();
+        if mirrored_hash < hash {
+            (mirrored_hash, true)
+        } else {
+            (hash, false)
+        }
+    }
+
+    /// Same position with the columns in reverse order, moves included
+    pub fn mirror(&self) -> Connect4 {
+        let mut mirror = Connect4::new();
+        for &col in &self.moves {
+            mirror.play(self.mirror_move(col));
+        }
+        mirror
+    }
+
+    /// Column matching `col` in the mirror image
+    pub fn mirror_move(&self, col: u32) -> u32 {
+        self.size.width - 1 - col
+    }
+
+    /// Hashes of the position and of its mirror image
+    fn zobrist_hashes(&self) -> (u64, u64) {
         // Use Zobrist hashing for better performance
+        // Keys come from a fixed seed and SplitMix64, so hashes are the same on every run, every thread
+        // and every version of the rand crate, books and position databases being keyed on them
         thread_local! {
             static ZOBRIST_TABLE: [[[u64; 3]; 7]; 6] = {
-                let mut rng = rand::thread_rng();
                 let mut table = [[[0; 3]; 7]; 6];
                 // Fill the table with random values
                 for i in 0..6 {
                     for j in 0..7 {
                         for k in 0..3 {
-                            table[i][j][k] = rng.gen();
+                            table[i][j][k] = derive_seed(ZOBRIST_SEED, (i * 21 + j * 3 + k) as u64);
                         }
                     }
                 }
@@ -283,6 +371,7 @@ impl Connect4 {
         }
 
         let mut hash: u64 = 0;
+        let mut mirrored_hash: u64 = 0;
         ZOBRIST_TABLE.with(|table| {
             // XOR the hash with the appropriate random number for each position
             for i in 0..self.size.height {
@@ -294,12 +383,13 @@ impl Connect4 {
                     };
                     if cell_value > 0 {
                         hash ^= table[i as usize][j as usize][cell_value - 1];
+                        mirrored_hash ^= table[i as usize][self.mirror_move(j) as usize][cell_value - 1];
                     }
                 }
             }
         });
 
-        hash
+        (hash, mirrored_hash)
     }
 }
 
@@ -399,6 +489,19 @@ mod tests {
         assert_eq!(game.is_someone_winning().unwrap(), Player::Red);
     }
 
+    #[test]
+    fn test_hash_is_reproducible() {
+        let mut game = Connect4::new();
+        game.play(3);
+        game.play(2);
+        let hash = game.get_hash();
+        let other_thread = std::thread::spawn(move || game.get_hash()).join().unwrap();
+        assert_eq!(hash, other_thread);
+
+        // Saved books and databases depend on the keys never changing
+        assert_eq!(bench::position("0").get_hash(), 0xf655_2cb2_32e0_f7d8);
+    }
+
     #[test]
     fn test_undo() -> Result<(), String> {
         let mut game = Connect4::new();
@@ -407,4 +510,97 @@ mod tests {
         assert_eq!(game.get_board(), [[CellState::Empty; 7]; 6]);
         Ok(())
     }
+
+    #[test]
+    fn test_legal_moves() {
+        let mut game = Connect4::new();
+        assert_eq!(game.legal_moves().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4, 5, 6]);
+        for _ in 0..6 {
+            game.play(3);
+        }
+        assert!(!game.is_legal(3));
+        assert!(game.is_legal(4));
+        assert!(!game.is_legal(7));
+        assert_eq!(game.legal_moves().collect::<Vec<_>>(), vec![0, 1, 2, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_perft() {
+        let game = Connect4::new();
+        let expected = [1, 7, 49, 343, 2401, 16807, 117649];
+        for (depth, &nodes) in expected.iter().enumerate() {
+            assert_eq!(game.perft(depth as u32), nodes);
+        }
+        // Red wins on the next move in column 0, that sequence stops there
+        let mut game = Connect4::new();
+        for col in [0, 1, 0, 1, 0, 1] {
+            game.play(col);
+        }
+        assert_eq!(game.perft(2), 6 * 7 + 1);
+    }
+
+    // Perft of a plain grid, sharing no code with `Connect4`
+    fn grid_perft(grid: &mut [Vec<u8>; 7], player: u8, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut nodes = 0;
+        for col in 0..7 {
+            if grid[col].len() == 6 {
+                continue;
+            }
+            grid[col].push(player);
+            nodes += if grid_connects_four(grid, col) { 1 } else { grid_perft(grid, 3 - player, depth - 1) };
+            grid[col].pop();
+        }
+        nodes
+    }
+
+    // Whether the top stone of `col` is part of four in a row
+    fn grid_connects_four(grid: &[Vec<u8>; 7], col: usize) -> bool {
+        let row = grid[col].len() as i32 - 1;
+        let player = grid[col][row as usize];
+        let owns = |c: i32, r: i32| {
+            (0..7).contains(&c) && r >= 0 && grid[c as usize].get(r as usize) == Some(&player)
+        };
+        [(1, 0), (0, 1), (1, 1), (1, -1)].iter().any(|&(dc, dr)| {
+            let count = |sign: i32| (1..4).take_while(|&i| owns(col as i32 + sign * i * dc, row + sign * i * dr)).count();
+            1 + count(1) + count(-1) >= 4
+        })
+    }
+
+    #[test]
+    fn test_perft_deep() {
+        let game = Connect4::new();
+        // No one connects four in 7 plies, but the 7 sequences filling a single column leave 6 moves
+        assert_eq!(game.perft(7), 7u64.pow(7) - 7);
+        let expected = grid_perft(&mut Default::default(), 1, 8);
+        assert_eq!(expected, 5686266);
+        assert_eq!(game.perft(8), expected);
+    }
+
+    #[test]
+    fn test_mirror() {
+        let mut game = Connect4::new();
+        for col in [0, 3, 1] {
+            game.play(col);
+        }
+        let mirror = game.mirror();
+        assert_eq!(mirror.get_moves(), &[6, 3, 5]);
+        assert_eq!(mirror.get_turn(), game.get_turn());
+        assert_eq!(mirror.mirror().get_hash(), game.get_hash());
+        assert_ne!(mirror.get_hash(), game.get_hash());
+
+        let (hash, mirrored) = game.canonical_hash();
+        let (mirror_hash, mirror_mirrored) = mirror.canonical_hash();
+        assert_eq!(hash, mirror_hash);
+        assert_ne!(mirrored, mirror_mirrored);
+
+        // A symmetric position is its own canonical form
+        let mut game = Connect4::new();
+        for col in [2, 3, 4] {
+            game.play(col);
+        }
+        assert_eq!(game.canonical_hash(), (game.get_hash(), false));
+    }
 }
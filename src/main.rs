@@ -1,6 +1,17 @@
 use std::env;
 use std::error::Error;
 
+use connect4::bench::{self, Speedup, BENCH_POSITIONS, DEFAULT_BENCH_DEPTH};
+use connect4::book::{BookSource, OpeningBook};
+use connect4::engine::EngineConfig;
+use connect4::engine::{EvalParams, SearchLimits};
+use connect4::hint::{self, DEFAULT_HINT_DEPTH};
+use connect4::puzzle::{PuzzleConfig, PuzzleSet, DEFAULT_PUZZLE_NODES};
+use connect4::review::{self, MoveClass, PlayerSummary, DEFAULT_REVIEW_DEPTH, DEFAULT_REVIEW_NODES};
+use connect4::skill;
+use connect4::tournament::{self, Format, Sprt, TournamentConfig};
+use connect4::tuning::{self, Label, TuneConfig};
+use connect4::wdl::{self, WdlModel};
 use connect4::Connect4;
 
 #[actix_web::main]
@@ -11,20 +22,389 @@ async fn main() -> std::io::Result<()> {
         println!("Starting Connect4 server...");
         connect4::server::run_server().await?;
         Ok(())
+    } else if args.len() > 1 && args[1] == "tournament" {
+        if let Err(e) = tournament_command(&args[2..]) {
+            println!("Error: {}", e);
+            println!("Usage: connect4 tournament [--gauntlet] [--threads N] [--openings PLIES] [--sprt ELO0,ELO1] [--seed N] ENGINE...");
+            println!("ENGINE is written minimax:depth=4,time=100, engines: {}", connect4::engine::ENGINE_NAMES.join(", "));
+        }
+        Ok(())
+    } else if args.len() > 1 && args[1] == "tune" {
+        if let Err(e) = tune_command(&args[2..]) {
+            println!("Error: {}", e);
+            println!("Usage: connect4 tune [--games N] [--random-plies N] [--engine ENGINE] [--label result|solver] [--iterations N] [--start FILE] [--out FILE] [--seed N]");
+        }
+        Ok(())
+    } else if args.len() > 1 && args[1] == "wdl" {
+        if let Err(e) = wdl_command(&args[2..]) {
+            println!("Error: {}", e);
+            println!("Usage: connect4 wdl [--games N] [--random-plies N] [--engine ENGINE] [--depth D] [--out FILE] [--seed N]");
+        }
+        Ok(())
+    } else if args.len() > 1 && args[1] == "calibrate" {
+        if let Err(e) = calibrate_command(&args[2..]) {
+            println!("Error: {}", e);
+            println!("Usage: connect4 calibrate [--openings PLIES] [--threads N] [--seed N]");
+        }
+        Ok(())
+    } else if args.len() > 1 && args[1] == "bench" {
+        if let Err(e) = bench_command(&args[2..]) {
+            println!("Error: {}", e);
+            println!("Usage: connect4 bench [--depth D] [--threads N]");
+        }
+        Ok(())
+    } else if args.len() > 1 && args[1] == "perft" {
+        match args.get(2).map(|depth| depth.parse::<u32>()) {
+            Some(Ok(depth)) => {
+                let game = Connect4::new();
+                for depth in 1..=depth {
+                    let start = std::time::Instant::now();
+                    let nodes = game.perft(depth);
+                    println!("Depth {}: {} nodes in {} ms", depth, nodes, start.elapsed().as_millis());
+                }
+            }
+            _ => println!("Usage: connect4 perft DEPTH"),
+        }
+        Ok(())
+    } else if args.len() > 1 && args[1] == "book" {
+        if let Err(e) = book_command(&args[2..]) {
+            println!("Error: {}", e);
+            println!("Usage: connect4 book [--plies N] [--depth D] [--solver NODES] [--out FILE]");
+        }
+        Ok(())
+    } else if args.len() > 1 && args[1] == "puzzles" {
+        if let Err(e) = puzzles_command(&args[2..]) {
+            println!("Error: {}", e);
+            println!("Usage: connect4 puzzles [--games N] [--random-plies N] [--engine ENGINE] [--max-moves N] [--solver NODES] [--out FILE] [--seed N]");
+        }
+        Ok(())
+    } else if args.len() > 1 && args[1] == "review" {
+        if let Err(e) = review_command(&args[2..]) {
+            println!("Error: {}", e);
+            println!("Usage: connect4 review MOVES [--depth D] [--solver NODES]");
+            println!("MOVES are the columns played, from 0, e.g. 3324");
+        }
+        Ok(())
     } else {
-        // Original console game
-        console_game();
+        // Original console game, the AI can be picked with --engine
+        let mut config = match args.iter().position(|arg| arg == "--engine") {
+            Some(i) => match args.get(i + 1).map(|spec| spec.parse::<EngineConfig>()) {
+                Some(Ok(config)) => config,
+                Some(Err(e)) => {
+                    println!("Error: {}", e);
+                    return Ok(());
+                }
+                None => {
+                    println!("Usage: connect4 [--engine ENGINE] [--seed N]");
+                    return Ok(());
+                }
+            },
+            None => EngineConfig { depth: Some(10), ..EngineConfig::new("minimax") },
+        };
+        if let Some(i) = args.iter().position(|arg| arg == "--seed") {
+            match args.get(i + 1).map(|seed| seed.parse::<u64>()) {
+                Some(Ok(seed)) => config.seed = Some(seed),
+                _ => {
+                    println!("Usage: connect4 [--engine ENGINE] [--seed N]");
+                    return Ok(());
+                }
+            }
+        }
+        console_game(&config);
         Ok(())
     }
 }
 
-fn console_game() {
+fn tournament_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut config = TournamentConfig {
+        participants: Vec::new(),
+        format: Format::RoundRobin,
+        opening_plies: 2,
+        threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        sprt: None,
+        seed: rand::random(),
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--gauntlet" => config.format = Format::Gauntlet,
+            "--threads" => config.threads = args.next().ok_or("Missing thread count")?.parse()?,
+            "--openings" => config.opening_plies = args.next().ok_or("Missing opening length")?.parse()?,
+            "--sprt" => {
+                let bounds = args.next().ok_or("Missing SPRT bounds")?;
+                let (elo0, elo1) = bounds.split_once(',').ok_or("SPRT bounds must be ELO0,ELO1")?;
+                config.sprt = Some(Sprt::new(elo0.parse()?, elo1.parse()?));
+            }
+            "--seed" => config.seed = args.next().ok_or("Missing seed")?.parse()?,
+            spec => config.participants.push(spec.parse()?),
+        }
+    }
+    if config.participants.len() < 2 {
+        return Err("At least two engines are required".into());
+    }
+
+    println!("Seed: {}", config.seed);
+    let pairings = tournament::run_tournament(&config);
+    tournament::print_report(&config, &pairings);
+    Ok(())
+}
+
+fn tune_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut config = TuneConfig {
+        games: 200,
+        random_plies: 4,
+        engine: "intermediate".parse()?,
+        label: Label::Result,
+        iterations: 100,
+        seed: rand::random(),
+    };
+    let mut start = EvalParams::default();
+    let mut out = "eval.json".to_string();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => config.games = args.next().ok_or("Missing game count")?.parse()?,
+            "--random-plies" => config.random_plies = args.next().ok_or("Missing random plies")?.parse()?,
+            "--engine" => config.engine = args.next().ok_or("Missing engine")?.parse()?,
+            "--label" => config.label = args.next().ok_or("Missing label")?.parse()?,
+            "--iterations" => config.iterations = args.next().ok_or("Missing iterations")?.parse()?,
+            "--start" => start = EvalParams::load(args.next().ok_or("Missing start file")?)?,
+            "--out" => out = args.next().ok_or("Missing output file")?.clone(),
+            "--seed" => config.seed = args.next().ok_or("Missing seed")?.parse()?,
+            arg => return Err(format!("Unknown argument: {}", arg).into()),
+        }
+    }
+
+    println!("Seed: {}", config.seed);
+    let samples = tuning::generate_samples(&config);
+    println!("Generated {} positions", samples.len());
+    let scale = tuning::fit_scale(&samples, &start);
+    println!("Starting error: {:.6}", tuning::error(&samples, &start, scale));
+    let (params, error) = tuning::tune(&samples, start, config.iterations, true);
+    params.save(&out)?;
+    println!("Final error: {:.6}, weights written to {}", error, out);
+    Ok(())
+}
+
+fn wdl_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut config = TuneConfig {
+        games: 200,
+        random_plies: 4,
+        engine: "intermediate".parse()?,
+        label: Label::Result,
+        iterations: 0,
+        seed: rand::random(),
+    };
+    let mut depth = 8;
+    let mut out = "wdl.json".to_string();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => config.games = args.next().ok_or("Missing game count")?.parse()?,
+            "--random-plies" => config.random_plies = args.next().ok_or("Missing random plies")?.parse()?,
+            "--engine" => config.engine = args.next().ok_or("Missing engine")?.parse()?,
+            "--depth" => depth = args.next().ok_or("Missing depth")?.parse()?,
+            "--out" => out = args.next().ok_or("Missing output file")?.clone(),
+            "--seed" => config.seed = args.next().ok_or("Missing seed")?.parse()?,
+            arg => return Err(format!("Unknown argument: {}", arg).into()),
+        }
+    }
+
+    println!("Seed: {}", config.seed);
+    let samples = tuning::generate_samples(&config);
+    println!("{} positions, searching them to depth {}", samples.len(), depth);
+    let scored = wdl::score_samples(&samples, depth);
+    let model = WdlModel::fit(&scored);
+    println!("Scale: {}, draw margin: {}, log loss: {:.6}", model.scale, model.draw_margin, model.log_loss(&scored));
+    model.save(&out)?;
+    println!("Model written to {}", out);
+    Ok(())
+}
+
+fn calibrate_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut opening_plies = 1;
+    let mut threads = 1;
+    let mut seed = rand::random();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--openings" => opening_plies = args.next().ok_or("Missing opening plies")?.parse()?,
+            "--threads" => threads = args.next().ok_or("Missing thread count")?.parse()?,
+            "--seed" => seed = args.next().ok_or("Missing seed")?.parse()?,
+            arg => return Err(format!("Unknown argument: {}", arg).into()),
+        }
+    }
+
+    println!("Seed: {}", seed);
+    for (level, elo) in skill::calibrate(opening_plies, threads, seed) {
+        println!("{:<12} Elo {:>6.0} (currently {})", level.name(), elo, level.params().elo);
+    }
+    Ok(())
+}
+
+fn bench_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut depth = DEFAULT_BENCH_DEPTH;
+    let mut threads = 1;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--depth" => depth = args.next().ok_or("Missing depth")?.parse()?,
+            "--threads" => threads = args.next().ok_or("Missing threads")?.parse()?,
+            arg => return Err(format!("Unknown argument: {}", arg).into()),
+        }
+    }
+
+    if threads > 1 {
+        let speedup = Speedup::measure(depth, threads);
+        println!("Depth: {}", depth);
+        println!("1 thread: {} ms, {} nodes", speedup.single.elapsed.as_millis(), speedup.single.nodes());
+        println!("{} threads: {} ms, {} nodes", threads, speedup.parallel.elapsed.as_millis(), speedup.parallel.nodes());
+        println!("Time to depth speedup: {:.2}x", speedup.time_ratio());
+        println!("Nodes ratio: {:.2}x", speedup.nodes_ratio());
+        return Ok(());
+    }
+
+    let result = bench::run(depth);
+    for (moves, search) in BENCH_POSITIONS.iter().zip(&result.searches) {
+        println!("Position {:<26} move {} score {:>5} nodes {}", format!("\"{}\"", moves), search.best_move, search.score, search.nodes);
+    }
+    println!("Depth: {}", depth);
+    println!("Total nodes: {}", result.nodes());
+    println!("Time: {} ms", result.elapsed.as_millis());
+    println!("Nodes/second: {}", result.nodes_per_second());
+    println!("Signature: {:016x}", result.signature());
+    Ok(())
+}
+
+fn book_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut plies = 4;
+    let mut depth = 8;
+    let mut solver_nodes = None;
+    let mut out = "book.json".to_string();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--plies" => plies = args.next().ok_or("Missing plies")?.parse()?,
+            "--depth" => depth = args.next().ok_or("Missing depth")?.parse()?,
+            "--solver" => solver_nodes = Some(args.next().ok_or("Missing solver nodes")?.parse()?),
+            "--out" => out = args.next().ok_or("Missing output file")?.clone(),
+            arg => return Err(format!("Unknown argument: {}", arg).into()),
+        }
+    }
+
+    let source = match solver_nodes {
+        Some(nodes) => BookSource::Solver { nodes, depth },
+        None => BookSource::Search { depth },
+    };
+    let book = OpeningBook::generate(plies, source, true);
+    book.save(&out)?;
+    println!("{} positions written to {}", book.positions.len(), out);
+    Ok(())
+}
+
+fn puzzles_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut config = PuzzleConfig {
+        games: 100,
+        random_plies: 4,
+        engine: "novice".parse()?,
+        max_moves: 4,
+        solver_nodes: DEFAULT_PUZZLE_NODES,
+        seed: rand::random(),
+    };
+    let mut out = "puzzles.json".to_string();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => config.games = args.next().ok_or("Missing game count")?.parse()?,
+            "--random-plies" => config.random_plies = args.next().ok_or("Missing random plies")?.parse()?,
+            "--engine" => config.engine = args.next().ok_or("Missing engine")?.parse()?,
+            "--max-moves" => config.max_moves = args.next().ok_or("Missing max moves")?.parse()?,
+            "--solver" => config.solver_nodes = args.next().ok_or("Missing solver nodes")?.parse()?,
+            "--out" => out = args.next().ok_or("Missing output file")?.clone(),
+            "--seed" => config.seed = args.next().ok_or("Missing seed")?.parse()?,
+            arg => return Err(format!("Unknown argument: {}", arg).into()),
+        }
+    }
+
+    println!("Seed: {}", config.seed);
+    let puzzles = PuzzleSet::generate(&config);
+    puzzles.save(&out)?;
+    println!("{} puzzles written to {}", puzzles.puzzles.len(), out);
+    Ok(())
+}
+
+fn review_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut depth = DEFAULT_REVIEW_DEPTH;
+    let mut solver_nodes = DEFAULT_REVIEW_NODES;
+    let mut moves = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--depth" => depth = args.next().ok_or("Missing depth")?.parse()?,
+            "--solver" => solver_nodes = args.next().ok_or("Missing solver nodes")?.parse()?,
+            arg if arg.starts_with("--") => return Err(format!("Unknown argument: {}", arg).into()),
+            arg => moves = Some(arg),
+        }
+    }
+
+    if depth < 1 {
+        return Err("Depth must be at least 1".into());
+    }
+    let moves = moves.ok_or("Missing moves")?;
+    if let Some(c) = moves.chars().find(|c| !('0'..='6').contains(c)) {
+        return Err(format!("Invalid column: {}", c).into());
+    }
+    let game = bench::position(moves);
+    if game.get_moves().len() != moves.len() {
+        return Err("Moves are not a legal game".into());
+    }
+
+    let review = review::review(&game, depth, solver_nodes);
+    for mv in &review.moves {
+        let class = match mv.class {
+            MoveClass::Best | MoveClass::Good => String::new(),
+            class => format!("{:?}", class).to_lowercase(),
+        };
+        println!("{:>2}. {:<8} played {} score {:>5}, best {} score {:>5} {}", mv.ply + 1, mv.player.to_string(), mv.column, mv.score, mv.best_move, mv.best_score, class);
+    }
+    print_summary("Red", &review.red);
+    print_summary("Yellow", &review.yellow);
+    Ok(())
+}
+
+fn print_summary(name: &str, summary: &PlayerSummary) {
+    println!(
+        "{}: accuracy {:.1}%, {} best, {} good, {} inaccuracies, {} mistakes, {} blunders",
+        name, summary.accuracy, summary.best, summary.good, summary.inaccuracies, summary.mistakes, summary.blunders
+    );
+}
+
+fn console_game(config: &EngineConfig) {
+    // Print the seed so the game can be replayed with --seed
+    let config = config.or_seed(rand::random());
+    println!("Seed: {}", config.seed.unwrap());
+    let mut engine = config.build().unwrap();
     let mut game = Connect4::new();
     game.print_board();
 
     loop {
         let play = match get_user_play() {
-            Ok(play) => play,
+            Ok(UserInput::Play(play)) => play,
+            Ok(UserInput::Hint(depth)) => {
+                let limits = SearchLimits { depth: depth.unwrap_or(DEFAULT_HINT_DEPTH), time: None, nodes: None };
+                if let Some(hint) = hint::hint(&game, &limits) {
+                    println!("Hint: play {}, {}", hint.column, hint.reason);
+                }
+                continue;
+            }
             Err(e) => {
                 println!("Error: {}", e);
                 continue;
@@ -40,7 +420,8 @@ fn console_game() {
                 println!("It's a draw!");
                 break;
             }
-            game.play_minimax(10);
+            game.play_engine(engine.as_mut(), &config.limits());
+            engine.ponder(&game, &config.limits());
             game.print_board();
             if let Some(player) = game.is_someone_winning() {
                 println!("Player {:?} wins!", player);
@@ -60,9 +441,20 @@ fn console_game() {
     std::io::stdin().read_line(&mut String::new()).unwrap();
 }
 
-fn get_user_play() -> Result<u32, Box<dyn Error>> {
-    println!("Enter a column number to play (0-6):");
+enum UserInput {
+    Play(u32),
+    /// Ask for a hint, searching to the given depth
+    Hint(Option<i32>),
+}
+
+fn get_user_play() -> Result<UserInput, Box<dyn Error>> {
+    println!("Enter a column number to play (0-6), or hint [DEPTH]:");
     let mut play = String::new();
     std::io::stdin().read_line(&mut play)?;
-    Ok(play.trim().parse()?)
+    let mut words = play.split_whitespace();
+    match words.next() {
+        Some("hint") => Ok(UserInput::Hint(words.next().map(str::parse).transpose()?)),
+        Some(col) => Ok(UserInput::Play(col.parse()?)),
+        None => Err("Missing column".into()),
+    }
 }
\ No newline at end of file
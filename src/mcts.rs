@@ -0,0 +1,266 @@
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+
+use crate::engine::{Engine, SearchLimits, SearchResult};
+use crate::solver::{self, Position};
+use crate::Connect4;
+
+/// Exploration constant of UCT, the usual sqrt(2)
+pub const DEFAULT_EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+/// Iterations per unit of depth when the limits only give a depth
+const ITERATIONS_PER_DEPTH: u64 = 2000;
+
+/// How games are finished from a new tree node
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Playout {
+    /// Uniformly random moves
+    Random,
+    /// Win when possible, block the opponent's immediate wins, otherwise random safe moves
+    Heuristic,
+}
+
+impl std::str::FromStr for Playout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Playout::Random),
+            "heuristic" => Ok(Playout::Heuristic),
+            _ => Err(format!("Unknown playout: {}", s)),
+        }
+    }
+}
+
+/// Monte Carlo tree search with the UCT selection rule
+/// The budget is `limits.nodes` iterations or `limits.time`, whichever ends first
+pub struct MctsEngine {
+    pub exploration: f64,
+    pub playout: Playout,
+    rng: StdRng,
+}
+
+impl MctsEngine {
+    pub fn new(seed: u64) -> MctsEngine {
+        MctsEngine {
+            exploration: DEFAULT_EXPLORATION,
+            playout: Playout::Random,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+struct Node {
+    col: u32,
+    parent: usize,
+    children: Vec<usize>,
+    untried: Vec<u32>,
+    visits: u32,
+    /// Total reward for the player who moved into this node
+    reward: f64,
+    /// Reward of the game if it ended with this node's move
+    terminal: Option<f64>,
+}
+
+impl Node {
+    fn new(col: u32, parent: usize, position: &Position, terminal: Option<f64>) -> Node {
+        let untried = if terminal.is_some() {
+            Vec::new()
+        } else {
+            (0..7).filter(|&col| position.can_play(col)).collect()
+        };
+        Node {
+            col,
+            parent,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            reward: 0.0,
+            terminal,
+        }
+    }
+}
+
+impl Engine for MctsEngine {
+    fn name(&self) -> &'static str {
+        "mcts"
+    }
+
+    fn choose_move(&mut self, game: &Connect4, limits: &SearchLimits) -> SearchResult {
+        let start = Instant::now();
+        let iterations = match (limits.nodes, limits.time) {
+            (Some(nodes), _) => nodes,
+            (None, Some(_)) => u64::MAX,
+            (None, None) => ITERATIONS_PER_DEPTH * limits.depth.max(1) as u64,
+        };
+
+        let mut rng = self.rng.clone();
+        let root_position = Position::from_game(game);
+        let mut tree = vec![Node::new(0, 0, &root_position, None)];
+        let mut max_depth = 0;
+
+        // At least one iteration, so that there is a move to pick whatever the budget
+        let mut iteration = 0;
+        while iteration == 0 || (iteration < iterations && limits.time.is_none_or(|limit| start.elapsed() < limit)) {
+            iteration += 1;
+            let mut position = root_position;
+            let mut node = 0;
+            let mut depth = 0;
+
+            // Selection
+            while tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+                node = self.select_child(&tree, node);
+                position.play(tree[node].col);
+                depth += 1;
+            }
+
+            // Expansion
+            if !tree[node].untried.is_empty() {
+                let index = rng.gen_range(0..tree[node].untried.len());
+                let col = tree[node].untried.swap_remove(index);
+                let terminal = move_result(&position, col);
+                position.play(col);
+                let child = tree.len();
+                tree.push(Node::new(col, node, &position, terminal));
+                tree[node].children.push(child);
+                node = child;
+                depth += 1;
+            }
+            max_depth = max_depth.max(depth);
+
+            // Simulation, from the point of view of the player who moved into `node`
+            let mut reward = match tree[node].terminal {
+                Some(reward) => reward,
+                None => 1.0 - self.simulate(position, &mut rng),
+            };
+
+            // Backpropagation
+            loop {
+                tree[node].visits += 1;
+                tree[node].reward += reward;
+                if node == 0 {
+                    break;
+                }
+                node = tree[node].parent;
+                reward = 1.0 - reward;
+            }
+        }
+
+        self.rng = rng;
+        let best = tree[0]
+            .children
+            .iter()
+            .map(|&child| &tree[child])
+            .max_by_key(|child| child.visits)
+            .unwrap();
+        SearchResult {
+            best_move: best.col,
+            score: ((best.reward / best.visits as f64 - 0.5) * 200.0) as i32,
+            depth: max_depth,
+            nodes: iteration,
+        }
+    }
+}
+
+impl MctsEngine {
+    fn select_child(&self, tree: &[Node], node: usize) -> usize {
+        let log_visits = (tree[node].visits as f64).ln();
+        let uct = |child: &Node| {
+            child.reward / child.visits as f64 + self.exploration * (log_visits / child.visits as f64).sqrt()
+        };
+        *tree[node]
+            .children
+            .iter()
+            .max_by(|&&a, &&b| uct(&tree[a]).total_cmp(&uct(&tree[b])))
+            .unwrap()
+    }
+
+    /// Play the game out and return the reward of the player to move in `position`
+    fn simulate(&self, mut position: Position, rng: &mut StdRng) -> f64 {
+        // The reward flips every ply, track whose turn it is compared to the start
+        let mut same_player = true;
+        loop {
+            let possible: Vec<u32> = (0..7).filter(|&col| position.can_play(col)).collect();
+            if possible.is_empty() {
+                return 0.5;
+            }
+
+            let col = match self.playout {
+                Playout::Random => *possible.choose(rng).unwrap(),
+                Playout::Heuristic => heuristic_move(&position, &possible, rng),
+            };
+            if let Some(reward) = move_result(&position, col) {
+                return if same_player { reward } else { 1.0 - reward };
+            }
+            position.play(col);
+            same_player = !same_player;
+        }
+    }
+}
+
+fn heuristic_move(position: &Position, possible: &[u32], rng: &mut StdRng) -> u32 {
+    if let Some(&col) = possible.iter().find(|&&col| position.is_winning_move(col)) {
+        return col;
+    }
+    let safe = position.possible_non_losing_moves();
+    let safe: Vec<u32> = possible
+        .iter()
+        .copied()
+        .filter(|&col| safe & solver::column_mask(col) != 0)
+        .collect();
+    // Blocking moves are the only safe ones when the opponent threatens to win
+    *safe.choose(rng).or_else(|| possible.choose(rng)).unwrap()
+}
+
+/// Reward for the player making `col` if it ends the game
+fn move_result(position: &Position, col: u32) -> Option<f64> {
+    if position.is_winning_move(col) {
+        Some(1.0)
+    } else if position.moves == solver::CELLS - 1 {
+        Some(0.5)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_takes_immediate_win() {
+        let mut game = Connect4::new();
+        for col in [0, 1, 0, 1, 0, 1] {
+            game.play(col);
+        }
+        let mut engine = MctsEngine::new(0);
+        let limits = SearchLimits { nodes: Some(2000), ..SearchLimits::depth(1) };
+        assert_eq!(engine.choose_move(&game, &limits).best_move, 0);
+    }
+
+    #[test]
+    fn test_blocks_immediate_loss() {
+        let mut game = Connect4::new();
+        for col in [0, 1, 0, 1, 0] {
+            game.play(col);
+        }
+        let mut engine = MctsEngine::new(0);
+        engine.playout = Playout::Heuristic;
+        let limits = SearchLimits { nodes: Some(5000), ..SearchLimits::depth(1) };
+        assert_eq!(engine.choose_move(&game, &limits).best_move, 0);
+    }
+
+    #[test]
+    fn test_zero_budget_still_moves() {
+        let game = Connect4::new();
+        let config: crate::engine::EngineConfig = "mcts:time=0".parse().unwrap();
+        let result = config.build().unwrap().choose_move(&game, &config.limits());
+        assert!(game.is_legal(result.best_move));
+        assert_eq!(result.nodes, 1);
+    }
+}
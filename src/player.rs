@@ -1,8 +1,11 @@
 use std::fmt::Display;
 
+use serde::Serialize;
+
 use crate::CellState;
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Player {
     Red,
     Yellow,
@@ -18,10 +21,17 @@ impl Display for Player {
 }
 
 impl Player {
-    pub fn to_cell_state(&self) -> CellState {
+    pub fn to_cell_state(self) -> CellState {
         match self {
             Player::Red => CellState::Red,
             Player::Yellow => CellState::Yellow,
         }
     }
+
+    pub fn opponent(self) -> Player {
+        match self {
+            Player::Red => Player::Yellow,
+            Player::Yellow => Player::Red,
+        }
+    }
 }
\ No newline at end of file
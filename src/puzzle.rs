@@ -0,0 +1,312 @@
+use std::collections::HashSet;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{derive_seed, EngineConfig};
+use crate::json;
+use crate::player::Player;
+use crate::solver::{self, Position, Solver};
+use crate::Connect4;
+
+/// Nodes the solver may spend on one position while mining, positions needing more are skipped
+pub const DEFAULT_PUZZLE_NODES: u64 = 100_000;
+
+pub struct PuzzleConfig {
+    /// Self-play games the positions are taken from
+    pub games: usize,
+    /// Random moves opening each game, for variety
+    pub random_plies: usize,
+    /// Engine playing both sides, it has to make mistakes for puzzles to show up
+    pub engine: EngineConfig,
+    /// Longest win kept, in moves of the side to move
+    pub max_moves: i32,
+    pub solver_nodes: u64,
+    pub seed: u64,
+}
+
+/// A position where the side to move wins by force with a single move
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Puzzle {
+    /// Canonical hash of the position, in hexadecimal
+    pub id: String,
+    pub moves: Vec<u32>,
+    /// Moves of the side to move up to the win, counting the winning one
+    pub win_in: i32,
+    /// The only move that keeps a forced win
+    pub solution: u32,
+    /// Rough difficulty, see `rating`
+    pub rating: u32,
+}
+
+/// Stored as JSON
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PuzzleSet {
+    pub puzzles: Vec<Puzzle>,
+}
+
+impl PuzzleSet {
+    /// Mine the positions of `config.games` self-play games for puzzles, easiest first
+    pub fn generate(config: &PuzzleConfig) -> PuzzleSet {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let mut solver = Solver::new();
+        let mut seen = HashSet::new();
+        let mut puzzles = Vec::new();
+
+        for index in 0..config.games {
+            let mut game = Connect4::new();
+            for _ in 0..config.random_plies {
+                let moves: Vec<u32> = game.legal_moves().collect();
+                game.play(*moves.choose(&mut rng).unwrap());
+            }
+
+            let mut engine = config.engine.or_seed(derive_seed(config.seed, index as u64)).build().unwrap();
+            while game.is_someone_winning().is_none() && !game.is_draw() {
+                if seen.insert(game.canonical_hash().0) {
+                    puzzles.extend(find_puzzle(&game, config.max_moves, config.solver_nodes, &mut solver));
+                }
+                game.play_engine(engine.as_mut(), &config.engine.limits());
+            }
+        }
+        puzzles.sort_by_key(|puzzle| puzzle.rating);
+        PuzzleSet { puzzles }
+    }
+
+    pub fn load(path: &str) -> Result<PuzzleSet, String> {
+        json::load(path, "puzzles")
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        json::save(self, path, false)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Puzzle> {
+        self.puzzles.iter().find(|puzzle| puzzle.id == id)
+    }
+
+    /// Puzzle rated closest to `rating`
+    pub fn closest(&self, rating: u32) -> Option<&Puzzle> {
+        self.puzzles.iter().min_by_key(|puzzle| puzzle.rating.abs_diff(rating))
+    }
+}
+
+/// `game` as a puzzle, when the side to move wins within `max_moves` moves with exactly one move
+/// Immediate wins are left out, they are no puzzle
+fn find_puzzle(game: &Connect4, max_moves: i32, nodes: u64, solver: &mut Solver) -> Option<Puzzle> {
+    if !Position::from_game(game).winning_columns().is_empty() {
+        return None;
+    }
+    // Shortest forced win, an aborted search can't tell
+    let moves = game.get_moves().len() as i32;
+    let mut win_in = None;
+    for n in 2..=max_moves {
+        if solver.try_score_at_least(game, solver::win_score(moves, n), nodes)? {
+            win_in = Some(n);
+            break;
+        }
+    }
+    let win_in = win_in?;
+
+    // Every other move has to let the win slip past `win_in` moves
+    let score = solver::win_score(moves, win_in);
+    let mut solution = None;
+    let mut safe_moves = 0;
+    for col in game.legal_moves() {
+        let mut child = game.clone();
+        child.play(col);
+        if keeps_score(&child, score, nodes, solver)? {
+            if solution.replace(col).is_some() {
+                return None;
+            }
+        } else if Position::from_game(&child).winning_columns().is_empty() {
+            safe_moves += 1;
+        }
+    }
+
+    Some(Puzzle {
+        id: format!("{:016x}", game.canonical_hash().0),
+        moves: game.get_moves().to_vec(),
+        win_in,
+        solution: solution?,
+        rating: rating(win_in, safe_moves),
+    })
+}
+
+/// Whether the side that just moved in `child` still scores at least `score`
+fn keeps_score(child: &Connect4, score: i32, nodes: u64, solver: &mut Solver) -> Option<bool> {
+    solver.try_score_at_least(child, 1 - score, nodes).map(|better| !better)
+}
+
+/// Longer wins are harder, and so are positions where many wrong moves don't lose right away
+fn rating(win_in: i32, safe_moves: u32) -> u32 {
+    800 + 200 * (win_in as u32 - 1) + 50 * safe_moves
+}
+
+/// Whether the side that just moved in `child` connects four within `moves` moves
+fn keeps_win(child: &Connect4, moves: i32, solver: &mut Solver) -> bool {
+    let score = solver::win_score(child.get_moves().len() as i32 - 1, moves);
+    !solver.score_at_least(child, 1 - score)
+}
+
+/// What happened to a move played in a puzzle
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AttemptStep {
+    /// The move keeps a win within the puzzle's moves
+    pub correct: bool,
+    /// The move connected four, the puzzle is done
+    pub solved: bool,
+    /// The engine's defence, when the puzzle goes on
+    pub reply: Option<u32>,
+    /// A move that wins, shown once the attempt failed
+    pub solution: Option<u32>,
+}
+
+/// A puzzle being played, the engine defending as long as it can
+/// It owns no solver, so that the attempts can share one
+pub struct PuzzleAttempt {
+    pub puzzle: Puzzle,
+    pub game: Connect4,
+    /// Side solving the puzzle
+    pub player: Player,
+    /// Moves left to the side solving to connect four
+    moves_left: i32,
+    finished: bool,
+}
+
+impl PuzzleAttempt {
+    pub fn new(puzzle: &Puzzle) -> PuzzleAttempt {
+        let mut game = Connect4::new();
+        for &col in &puzzle.moves {
+            game.play(col);
+        }
+        PuzzleAttempt {
+            puzzle: puzzle.clone(),
+            player: *game.get_turn(),
+            game,
+            moves_left: puzzle.win_in,
+            finished: false,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Play `col` for the side solving, and the engine's reply when the move was right
+    /// Any move still winning in time is accepted, not only the stored solution
+    pub fn play(&mut self, col: u32, solver: &mut Solver) -> Result<AttemptStep, String> {
+        if self.finished {
+            return Err("Puzzle is over".to_string());
+        }
+        if !self.game.is_legal(col) {
+            return Err("Invalid move".to_string());
+        }
+
+        let mut child = self.game.clone();
+        child.play(col);
+        if child.is_someone_winning().is_some() {
+            self.game = child;
+            self.finished = true;
+            return Ok(AttemptStep { correct: true, solved: true, reply: None, solution: None });
+        }
+        if self.moves_left == 1 || !keeps_win(&child, self.moves_left, solver) {
+            self.finished = true;
+            let solution = self.winning_move(solver);
+            return Ok(AttemptStep { correct: false, solved: false, reply: None, solution });
+        }
+
+        self.game = child;
+        self.moves_left -= 1;
+        let reply = self.defence(solver);
+        self.game.play(reply);
+        Ok(AttemptStep { correct: true, solved: false, reply: Some(reply), solution: None })
+    }
+
+    /// A move of the side solving that still wins in time
+    fn winning_move(&self, solver: &mut Solver) -> Option<u32> {
+        self.game.legal_moves().find(|&col| {
+            let mut child = self.game.clone();
+            child.play(col);
+            child.is_someone_winning().is_some() || keeps_win(&child, self.moves_left, solver)
+        })
+    }
+
+    /// The defence holding out the longest, the center first on ties
+    fn defence(&self, solver: &mut Solver) -> u32 {
+        let mut moves: Vec<u32> = self.game.legal_moves().collect();
+        moves.sort_by_key(|&col| col.abs_diff(self.game.mirror_move(col)));
+        let mut best = (moves[0], 0);
+        for col in moves {
+            let mut child = self.game.clone();
+            child.play(col);
+            let stones = child.get_moves().len() as i32;
+            let win_in = (1..self.moves_left)
+                .find(|&n| solver.score_at_least(&child, solver::win_score(stones, n)))
+                .unwrap_or(self.moves_left);
+            if win_in > best.1 {
+                best = (col, win_in);
+            }
+        }
+        best.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::position;
+
+    fn puzzle(moves: &str) -> Option<Puzzle> {
+        find_puzzle(&position(moves), 4, DEFAULT_PUZZLE_NODES, &mut Solver::new())
+    }
+
+    #[test]
+    fn test_find_puzzle() {
+        // Red holds 2 and 3 on the bottom row, 1 and 4 both make an open three
+        assert_eq!(puzzle("2233"), None);
+        // Yellow took both edges, only 4 leaves an open three
+        let found = puzzle("2036").unwrap();
+        assert_eq!((found.solution, found.win_in), (4, 2));
+        // Red wins right away, no puzzle there
+        assert_eq!(puzzle("010106"), None);
+    }
+
+    #[test]
+    fn test_attempt() {
+        let found = puzzle("2036").unwrap();
+        let solver = &mut Solver::new();
+        let mut attempt = PuzzleAttempt::new(&found);
+        assert_eq!(attempt.player, Player::Red);
+        let step = attempt.play(4, solver).unwrap();
+        assert!(step.correct && !step.solved);
+        let reply = step.reply.unwrap();
+        let win = if reply == 5 { 1 } else { 5 };
+        assert!(attempt.play(win, solver).unwrap().solved);
+        assert!(attempt.play(0, solver).is_err());
+
+        let mut attempt = PuzzleAttempt::new(&found);
+        let step = attempt.play(0, solver).unwrap();
+        assert_eq!((step.correct, step.solution), (false, Some(4)));
+    }
+
+    #[test]
+    fn test_generate() {
+        let config = PuzzleConfig {
+            games: 4,
+            random_plies: 4,
+            engine: "novice".parse().unwrap(),
+            max_moves: 3,
+            solver_nodes: 20_000,
+            seed: 1,
+        };
+        let set = PuzzleSet::generate(&config);
+        assert!(!set.puzzles.is_empty());
+        let mut solver = Solver::new();
+        for found in &set.puzzles {
+            let step = PuzzleAttempt::new(found).play(found.solution, &mut solver).unwrap();
+            assert!(step.correct);
+        }
+    }
+}
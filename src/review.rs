@@ -0,0 +1,156 @@
+use serde::Serialize;
+
+use crate::book::{self, BookSource};
+use crate::player::Player;
+use crate::solver::Solver;
+use crate::transposition::TranspositionTable;
+use crate::Connect4;
+
+/// How much a move gave away compared to the best one
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MoveClass {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl MoveClass {
+    /// Class of a move scoring `loss` below the best move, on the `evaluate_board` scale
+    /// Giving away a forced result is always a blunder, a slower win only loses a few points
+    pub fn from_loss(loss: i32) -> MoveClass {
+        match loss {
+            i32::MIN..=0 => MoveClass::Best,
+            1..=10 => MoveClass::Good,
+            11..=30 => MoveClass::Inaccuracy,
+            31..=100 => MoveClass::Mistake,
+            _ => MoveClass::Blunder,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MoveReview {
+    /// Index of the move in the game, from 0
+    pub ply: usize,
+    pub player: Player,
+    pub column: u32,
+    /// Score of the move played, for the player who played it
+    pub score: i32,
+    pub best_move: u32,
+    pub best_score: i32,
+    pub loss: i32,
+    pub class: MoveClass,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PlayerSummary {
+    /// Average of the move accuracies, 100 when every move was the best
+    pub accuracy: f64,
+    pub best: usize,
+    pub good: usize,
+    pub inaccuracies: usize,
+    pub mistakes: usize,
+    pub blunders: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Review {
+    pub moves: Vec<MoveReview>,
+    pub red: PlayerSummary,
+    pub yellow: PlayerSummary,
+}
+
+/// Search depth used when the solver runs out of nodes
+pub const DEFAULT_REVIEW_DEPTH: i32 = 8;
+/// Solver budget per move, enough to solve most positions past the opening
+pub const DEFAULT_REVIEW_NODES: u64 = 200_000;
+
+/// Loss at which a move's accuracy drops to 1/e, most of a mistake
+const ACCURACY_SCALE: f64 = 50.0;
+
+/// Score every move of `game` against the best move of its position
+/// Positions are solved when the solver manages within `solver_nodes`, searched to `depth` otherwise
+/// Moves played after the game was won are not reviewed
+pub fn review(game: &Connect4, depth: i32, solver_nodes: u64) -> Review {
+    let table = TranspositionTable::default();
+    let mut solver = Solver::new();
+    let mut position = Connect4::new();
+    let mut moves = Vec::new();
+
+    for (ply, &column) in game.get_moves().iter().enumerate() {
+        if position.is_someone_winning().is_some() {
+            break;
+        }
+        let player = *position.get_turn();
+        let scores = book::score_position(&mut position, BookSource::Solver { nodes: solver_nodes, depth }, &table, &mut solver);
+        let best = scores[0];
+        let score = scores.iter().find(|mv| mv.col == column).map_or(best.score, |mv| mv.score);
+        let loss = best.score - score;
+        moves.push(MoveReview {
+            ply,
+            player,
+            column,
+            score,
+            best_move: best.col,
+            best_score: best.score,
+            loss,
+            class: MoveClass::from_loss(loss),
+        });
+        position.play(column);
+    }
+
+    let red = summarize(moves.iter().filter(|mv| mv.player == Player::Red));
+    let yellow = summarize(moves.iter().filter(|mv| mv.player == Player::Yellow));
+    Review { moves, red, yellow }
+}
+
+fn summarize<'a>(moves: impl Iterator<Item = &'a MoveReview>) -> PlayerSummary {
+    let mut summary = PlayerSummary::default();
+    let mut count = 0;
+    for mv in moves {
+        count += 1;
+        summary.accuracy += 100.0 * (-mv.loss as f64 / ACCURACY_SCALE).exp();
+        match mv.class {
+            MoveClass::Best => summary.best += 1,
+            MoveClass::Good => summary.good += 1,
+            MoveClass::Inaccuracy => summary.inaccuracies += 1,
+            MoveClass::Mistake => summary.mistakes += 1,
+            MoveClass::Blunder => summary.blunders += 1,
+        }
+    }
+    if count > 0 {
+        summary.accuracy /= count as f64;
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::position;
+
+    #[test]
+    fn test_review_flags_missed_win() {
+        // Red stacks three in column 0, yellow doesn't block and red doesn't take the win
+        let review = review(&position("0101063"), 4, 100_000);
+        assert_eq!(review.moves.len(), 7);
+        assert_eq!(review.moves[5].class, MoveClass::Blunder);
+        assert_eq!(review.moves[5].best_move, 0);
+        assert_eq!(review.moves[6].class, MoveClass::Blunder);
+        assert_eq!(review.moves[6].best_move, 0);
+        assert_eq!(review.yellow.blunders, 1);
+        assert!(review.red.accuracy < 100.0 && review.red.accuracy > review.yellow.accuracy);
+    }
+
+    #[test]
+    fn test_move_classes() {
+        assert_eq!(MoveClass::from_loss(0), MoveClass::Best);
+        assert_eq!(MoveClass::from_loss(2), MoveClass::Good);
+        assert_eq!(MoveClass::from_loss(20), MoveClass::Inaccuracy);
+        assert_eq!(MoveClass::from_loss(60), MoveClass::Mistake);
+        assert_eq!(MoveClass::from_loss(900), MoveClass::Blunder);
+    }
+}
@@ -1,22 +1,112 @@
+use crate::engine::{derive_seed, plies_to_win, Engine, EngineConfig, SearchLimits};
 use crate::evaluator::evaluate_position;
+use crate::hint::{hint, DEFAULT_HINT_DEPTH};
+use crate::player::Player;
+use crate::puzzle::{AttemptStep, PuzzleAttempt, PuzzleConfig, PuzzleSet, DEFAULT_PUZZLE_NODES};
+use crate::review::{review, DEFAULT_REVIEW_DEPTH, DEFAULT_REVIEW_NODES};
+use crate::solver::{self, Solver};
+use crate::transposition::TranspositionTable;
+use crate::wdl::{Wdl, WdlModel};
 use crate::Connect4;
 use actix_cors::Cors;
 use actix_web::{
     delete, get, post, web, App, HttpResponse, HttpServer
 };
 use once_cell::sync::Lazy;
+use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 struct GameAndDifficulty {
     game: Connect4,
-    difficulty: i32,
+    difficulty: Option<i32>, // Overrides the depth of the engine's limits, 0 for no AI
+    config: EngineConfig,
+    seed: u64, // Seeds the game's engine, one-off engines are seeded from this and the move number
+    ai: Option<Box<dyn Engine>>, // Kept while the game goes on so its search tables carry over between moves
+    ai_resigns: bool, // The AI resigns once the solver proves it lost, see `is_hopeless`
+    resigned: Option<Player>,
+    draw_agreed: bool,
 }
 
-// Store active games in a thread-safe HashMap
-static GAMES: Lazy<Mutex<HashMap<String, GameAndDifficulty>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+impl GameAndDifficulty {
+    fn termination(&self) -> Option<Termination> {
+        if self.resigned.is_some() {
+            Some(Termination::Resignation)
+        } else if self.draw_agreed {
+            Some(Termination::DrawAgreed)
+        } else if self.game.is_someone_winning().is_some() {
+            Some(Termination::FourInARow)
+        } else if self.game.is_draw() {
+            Some(Termination::BoardFull)
+        } else {
+            None
+        }
+    }
+
+    fn winner(&self) -> Option<Player> {
+        self.game.is_someone_winning().or(self.resigned.map(Player::opponent))
+    }
+
+    // A finished game keeps its moves but no longer needs the engine and its tables
+    fn drop_engine_if_over(&mut self) {
+        if self.termination().is_some() {
+            self.ai = None;
+        }
+    }
+}
+
+// How a game ended
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Termination {
+    FourInARow,
+    BoardFull,
+    Resignation,
+    DrawAgreed,
+}
+
+// Nodes the solver may spend deciding whether the AI resigns or accepts a draw
+const ADJUDICATION_NODES: u64 = 2_000_000;
+// The AI resigns once it is proven to lose within this many plies
+const RESIGN_PLIES: i32 = 6;
+
+// Store active games in a thread-safe HashMap, with the time of their last move
+// Games left for `GAME_TTL` are removed, and the oldest one once there are `MAX_GAMES`
+static GAMES: Lazy<Mutex<HashMap<String, (Instant, GameAndDifficulty)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+const GAME_TTL: Duration = Duration::from_secs(60 * 60);
+const MAX_GAMES: usize = 256;
+
+// Puzzles written by `connect4 puzzles`, a small set is mined on first use when the file is missing
+const PUZZLE_FILE: &str = "puzzles.json";
+static PUZZLES: Lazy<Result<PuzzleSet, String>> = Lazy::new(|| {
+    // A file that can't be read or parsed is reported, only a missing one is replaced
+    match std::fs::metadata(PUZZLE_FILE) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("No {}, mining puzzles...", PUZZLE_FILE);
+            Ok(PuzzleSet::generate(&PuzzleConfig {
+                games: 20,
+                random_plies: 4,
+                engine: "novice".parse().unwrap(),
+                max_moves: 4,
+                solver_nodes: DEFAULT_PUZZLE_NODES,
+                seed: 0,
+            }))
+        }
+        _ => PuzzleSet::load(PUZZLE_FILE),
+    }
+});
+// Model written by `connect4 wdl`, the built-in fit when the file is missing
+const WDL_FILE: &str = "wdl.json";
+static WDL: Lazy<WdlModel> = Lazy::new(|| WdlModel::load(WDL_FILE).unwrap_or_default());
+// Puzzles being played with the time they started, removed once solved, failed or left for `ATTEMPT_TTL`
+static ATTEMPTS: Lazy<Mutex<HashMap<String, (Instant, PuzzleAttempt)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+const ATTEMPT_TTL: Duration = Duration::from_secs(60 * 60);
+const MAX_ATTEMPTS: usize = 10_000;
+// Checks the moves of every attempt, its table only caches what it solved
+static PUZZLE_SOLVER: Lazy<Mutex<Solver>> = Lazy::new(|| Mutex::new(Solver::new()));
 
 #[derive(Serialize)]
 struct GameResponse {
@@ -26,6 +116,16 @@ struct GameResponse {
     winner: Option<String>,
     is_draw: bool,
     last_move: Option<u32>, // Last move made by the player
+    moves: Vec<u32>, // Every move played so far, in order
+    seed: u64, // Replays the same AI decisions when given back to POST /games
+    termination: Option<Termination>, // How the game ended, unset while it goes on
+}
+
+#[derive(Serialize)]
+struct DrawResponse {
+    accepted: bool, // The AI only accepts when the solver proves the game a draw
+    #[serde(flatten)]
+    game: GameResponse,
 }
 
 #[derive(Serialize)]
@@ -42,11 +142,18 @@ struct SuccessResponse {
 struct MoveRequest {
     column: u32,
     ai_difficulty: Option<i32>, // AI difficulty level
+    ai_engine: Option<String>, // AI engine name, see `engine::ENGINE_NAMES`, or a spec like minimax:depth=8,hash=64
+    seed: Option<u64>, // Overrides the seed of this AI move
 }
 
 #[derive(Deserialize)]
 struct NewGameRequest {
     ai_difficulty: Option<i32>,
+    ai_engine: Option<String>,
+    red: Option<EngineConfig>, // When both engines are set, the server plays the whole game
+    yellow: Option<EngineConfig>,
+    seed: Option<u64>, // Random when unset
+    ai_resigns: Option<bool>, // The AI resigns hopeless positions, off when unset
 }
 
 #[derive(Deserialize)]
@@ -54,13 +161,61 @@ struct EvaluateRequest {
     depth: i32,
 }
 
+#[derive(Deserialize)]
+struct HintRequest {
+    depth: Option<i32>, // Search budget, the depth defaults to `hint::DEFAULT_HINT_DEPTH`
+    time_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ReviewRequest {
+    depth: Option<i32>, // Search depth for the positions the solver can't finish
+    solver_nodes: Option<u64>, // Solver budget per move
+}
+
+#[derive(Serialize)]
+struct EvaluateResponse {
+    score: i32, // From the point of view of the player to move
+    plies_to_win: Option<i32>, // Set when the search finds a forced result, negative when the player to move loses
+    wdl: Wdl, // Chances of the player to move, from the score
+}
+
+#[derive(Deserialize)]
+struct NextPuzzleRequest {
+    rating: Option<u32>, // Picks the puzzle rated closest, a random one when unset
+}
+
+#[derive(Serialize)]
+struct PuzzleResponse {
+    id: String, // Attempt id, for POST /puzzles/{id}/attempt
+    puzzle: String,
+    board: Vec<Vec<String>>,
+    moves: Vec<u32>,
+    player: Player, // Side to move, the one winning
+    win_in: i32, // Moves of the side to move up to the win, counting the winning one
+    rating: u32,
+}
+
+#[derive(Deserialize)]
+struct AttemptRequest {
+    column: u32,
+}
+
+#[derive(Serialize)]
+struct AttemptResponse {
+    #[serde(flatten)]
+    step: AttemptStep,
+    board: Vec<Vec<String>>,
+    moves: Vec<u32>,
+}
+
 #[derive(Serialize)]
 struct GameListResponse {
     games: Vec<String>,
 }
 
-// Convert game state to a serializable response
-fn game_to_response(game: &Connect4, id: &str, last_move: Option<u32>) -> GameResponse {
+// Board cells as "empty", "red" or "yellow"
+fn board_response(game: &Connect4) -> Vec<Vec<String>> {
     let board = game.get_board();
     let mut board_response = Vec::new();
     
@@ -75,38 +230,190 @@ fn game_to_response(game: &Connect4, id: &str, last_move: Option<u32>) -> GameRe
         }
         board_response.push(row_response);
     }
-    
-    let winner = game.is_someone_winning().map(|player| match player {
+    board_response
+}
+
+// Convert game state to a serializable response
+fn game_to_response(game_and_difficulty: &GameAndDifficulty, id: &str, last_move: Option<u32>) -> GameResponse {
+    let game = &game_and_difficulty.game;
+    let winner = game_and_difficulty.winner().map(|player| match player {
         crate::player::Player::Red => "red".to_string(),
         crate::player::Player::Yellow => "yellow".to_string(),
     });
     
     GameResponse {
         id: id.to_string(),
-        board: board_response,
+        board: board_response(game),
         turn: match game.get_turn() {
             crate::player::Player::Red => "red".to_string(),
             crate::player::Player::Yellow => "yellow".to_string(),
         },
         winner,
-        is_draw: game.is_draw(),
+        is_draw: game.is_draw() || game_and_difficulty.draw_agreed,
         last_move,
+        moves: game.get_moves().to_vec(),
+        seed: game_and_difficulty.seed,
+        termination: game_and_difficulty.termination(),
+    }
+}
+
+// The game after the move of `req`, when the AI has to answer it and may resign
+fn resign_check(game: &GameAndDifficulty, req: &MoveRequest) -> Option<Connect4> {
+    if !game.ai_resigns || game.termination().is_some() || req.ai_difficulty.or(game.difficulty) == Some(0) {
+        return None;
+    }
+    let mut position = game.game.clone();
+    let over = !position.play(req.column) || position.is_someone_winning().is_some() || position.is_draw();
+    (!over).then_some(position)
+}
+
+// Whether the player to move is proven to lose within `RESIGN_PLIES`
+fn is_hopeless(game: &Connect4) -> bool {
+    let moves = game.get_moves().len() as i32;
+    Solver::new()
+        .try_solve(game, ADJUDICATION_NODES)
+        .is_some_and(|score| score < 0 && solver::plies_to_end(score, moves) <= RESIGN_PLIES)
+}
+
+// Whether the solver proves neither side can win
+fn is_dead_draw(game: &Connect4) -> bool {
+    Solver::new().try_solve(game, ADJUDICATION_NODES) == Some(0)
+}
+
+// Engine settings clients can set, the others name files on the server, write to its log or search in the background
+const CLIENT_SETTINGS: [&str; 11] = [
+    "depth", "time", "nodes", "c", "playout", "seed", "hash", "threads", "ordering", "algorithm", "book_margin",
+];
+// Largest resources an engine of a client can take
+const MAX_CLIENT_HASH_MB: usize = 64;
+const MAX_CLIENT_THREADS: usize = 4;
+const MAX_CLIENT_NODES: u64 = 1_000_000;
+// Largest search an engine of a client can take per move, an AI match plays up to 42 of them
+const MAX_CLIENT_DEPTH: i32 = 12;
+const MAX_CLIENT_TIME_MS: u64 = 2_000;
+
+// Parse an engine spec sent by a client, its keys checked before parsing since that opens files
+fn client_engine(spec: &str) -> Result<EngineConfig, String> {
+    let (_, settings) = spec.split_once(':').unwrap_or((spec, ""));
+    for setting in settings.split(',').filter(|s| !s.is_empty()) {
+        let key = setting.split_once('=').map_or(setting, |(key, _)| key);
+        if !CLIENT_SETTINGS.contains(&key) {
+            return Err(format!("Engine setting not allowed: {}", key));
+        }
+    }
+    let config = spec.parse()?;
+    check_client_engine(&config)?;
+    Ok(config)
+}
+
+// Refuse engines of clients taking more memory, threads, nodes, depth or time than the server gives
+fn check_client_engine(config: &EngineConfig) -> Result<(), String> {
+    if config.depth.is_some_and(|depth| !(1..=MAX_CLIENT_DEPTH).contains(&depth)) {
+        return Err(format!("Engine depth must be between 1 and {}", MAX_CLIENT_DEPTH));
+    }
+    if config.time_ms.is_some_and(|time_ms| !(1..=MAX_CLIENT_TIME_MS).contains(&time_ms)) {
+        return Err(format!("Engine time must be between 1 and {} ms", MAX_CLIENT_TIME_MS));
+    }
+    // A ponder search would keep running between requests
+    if config.ponder == Some(true) {
+        return Err("Engines can't ponder on the server".to_string());
+    }
+    if config.hash_mb.is_some_and(|hash_mb| hash_mb > MAX_CLIENT_HASH_MB) {
+        return Err(format!("Hash size must be at most {} MB", MAX_CLIENT_HASH_MB));
+    }
+    if config.threads.is_some_and(|threads| threads > MAX_CLIENT_THREADS) {
+        return Err(format!("Engine threads must be at most {}", MAX_CLIENT_THREADS));
+    }
+    if config.nodes.is_some_and(|nodes| nodes > MAX_CLIENT_NODES) {
+        return Err(format!("Engine nodes must be at most {}", MAX_CLIENT_NODES));
     }
+    Ok(())
+}
+
+// Check an AI difficulty sent by a client, the depth of the AI's search
+fn check_difficulty(difficulty: Option<i32>) -> Result<(), String> {
+    if difficulty.is_some_and(|difficulty| !(0..=MAX_CLIENT_DEPTH).contains(&difficulty)) {
+        return Err(format!("AI difficulty must be between 0 and {}", MAX_CLIENT_DEPTH));
+    }
+    Ok(())
+}
+
+// Play a whole game between two engines
+fn play_ai_match(red: &EngineConfig, yellow: &EngineConfig, seed: u64) -> Connect4 {
+    let mut game = Connect4::new();
+    let mut red_engine = red.or_seed(derive_seed(seed, 0)).build().unwrap();
+    let mut yellow_engine = yellow.or_seed(derive_seed(seed, 1)).build().unwrap();
+    while game.is_someone_winning().is_none() && !game.is_draw() {
+        match game.get_turn() {
+            crate::player::Player::Red => game.play_engine(red_engine.as_mut(), &red.limits()),
+            crate::player::Player::Yellow => game.play_engine(yellow_engine.as_mut(), &yellow.limits()),
+        };
+    }
+    game
 }
 
 // Create a new game
 #[post("/games")]
 async fn create_game(_req: web::Json<NewGameRequest>) -> HttpResponse {
     println!("Creating a new game...");
-    let game = Connect4::new();
+    let req = _req.into_inner();
     let id = Uuid::new_v4().to_string();
+    let difficulty = req.ai_difficulty;
+    let seed = req.seed.unwrap_or_else(rand::random);
+    let engine = req.ai_engine.unwrap_or_else(|| "minimax".to_string());
+    let config = match check_difficulty(difficulty).and_then(|_| client_engine(&engine)) {
+        Ok(config) => config,
+        Err(error) => return HttpResponse::BadRequest().json(ErrorResponse { error }),
+    };
 
-    let difficulty = _req.ai_difficulty.unwrap_or(5);
+    // An AI match is over once created, only a game left to play gets an engine
+    let (game, ai) = match (req.red, req.yellow) {
+        (None, None) => match config.or_seed(seed).build() {
+            Ok(ai) => (Connect4::new(), Some(ai)),
+            Err(error) => return HttpResponse::BadRequest().json(ErrorResponse { error }),
+        },
+        (Some(red), Some(yellow)) => {
+            let checked = [&red, &yellow]
+                .into_iter()
+                .try_for_each(|config| check_client_engine(config).and_then(|_| config.validate()));
+            if let Err(error) = checked {
+                return HttpResponse::BadRequest().json(ErrorResponse { error });
+            }
+            println!("Playing AI match for game {}...", id);
+            // The match can take a while, keep it off the async workers
+            match web::block(move || play_ai_match(&red, &yellow, seed)).await {
+                Ok(game) => (game, None),
+                Err(_) => {
+                    return HttpResponse::InternalServerError().json(ErrorResponse {
+                        error: "AI match failed".to_string(),
+                    });
+                }
+            }
+        }
+        _ => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: "Both red and yellow engines are required for an AI match".to_string(),
+            });
+        }
+    };
+
+    let last_move = game.get_moves().last().copied();
     let mut games = GAMES.lock().unwrap();
-    games.insert(id.clone(), GameAndDifficulty { game, difficulty });
+    let ai_resigns = req.ai_resigns.unwrap_or(false);
+    let game = GameAndDifficulty {
+        game,
+        difficulty,
+        config,
+        seed,
+        ai,
+        ai_resigns,
+        resigned: None,
+        draw_agreed: false,
+    };
+    store_expiring(&mut games, id.clone(), Instant::now(), game, GAME_TTL, MAX_GAMES);
     
-    let game_ref = &games.get(&id).unwrap().game;
-    HttpResponse::Created().json(game_to_response(game_ref, &id, None))
+    let (_, game_ref) = games.get(&id).unwrap();
+    HttpResponse::Created().json(game_to_response(game_ref, &id, last_move))
 }
 
 // Get game state
@@ -115,8 +422,8 @@ async fn get_game(path: web::Path<String>) -> HttpResponse {
     let id = path.into_inner();
     
     let games = GAMES.lock().unwrap();
-    if let Some(game) = games.get(&id) {
-        HttpResponse::Ok().json(game_to_response(&game.game, &id, None))
+    if let Some((_, game)) = games.get(&id) {
+        HttpResponse::Ok().json(game_to_response(game, &id, None))
     } else {
         HttpResponse::NotFound().json(ErrorResponse {
             error: "Game not found".to_string(),
@@ -137,49 +444,157 @@ async fn list_games() -> HttpResponse {
 #[post("/games/{id}/move")]
 async fn make_move(path: web::Path<String>, req: web::Json<MoveRequest>) -> HttpResponse {
     let id = path.into_inner();
-    
+
+    // A lost AI may resign rather than play on, which takes the solver
+    // Keep it off the async workers and the games lock, on a copy of the game after the move
+    let position = GAMES.lock().unwrap().get(&id).and_then(|(_, game)| resign_check(game, &req));
+    let hopeless = match position {
+        Some(position) => match web::block(move || (is_hopeless(&position), position)).await {
+            Ok(result) => Some(result),
+            Err(_) => {
+                return HttpResponse::InternalServerError().json(ErrorResponse {
+                    error: "Move failed".to_string(),
+                });
+            }
+        },
+        None => None,
+    };
+
     let mut games = GAMES.lock().unwrap();
-    if let Some(game) = games.get_mut(&id) {
-        if game.game.is_someone_winning().is_some() || game.game.is_draw() {
+    if let Some((last_played, game)) = games.get_mut(&id) {
+        if game.termination().is_some() {
             return HttpResponse::BadRequest().json(ErrorResponse {
                 error: "Game is already over".to_string(),
             });
         }
         
+        // Use the provided difficulty or default to game's default
+        let difficulty = req.ai_difficulty.or(game.difficulty);
+        if let Err(error) = check_difficulty(difficulty) {
+            return HttpResponse::BadRequest().json(ErrorResponse { error });
+        }
+
+        // Overriding the engine or the seed plays this move with a one-off engine
+        // Seeding it from the move number lets the same moves replay the same AI decisions
+        let mut one_off = None;
+        let mut limits = game.config.limits();
+        if req.ai_engine.is_some() || req.seed.is_some() {
+            let seed = req.seed.unwrap_or_else(|| derive_seed(game.seed, game.game.get_moves().len() as u64));
+            let config = match req.ai_engine.as_deref().map(client_engine) {
+                Some(Ok(config)) => config,
+                Some(Err(error)) => return HttpResponse::BadRequest().json(ErrorResponse { error }),
+                None => game.config.clone(),
+            };
+            limits = config.limits();
+            match (EngineConfig { seed: Some(seed), ..config }).build() {
+                Ok(engine) => one_off = Some(engine),
+                Err(error) => return HttpResponse::BadRequest().json(ErrorResponse { error }),
+            }
+        }
+        // The difficulty only sets the depth, the time and nodes of the engine still apply
+        if let Some(depth) = difficulty {
+            limits.depth = depth;
+        }
+
         if !game.game.play(req.column) {
             return HttpResponse::BadRequest().json(ErrorResponse {
                 error: "Invalid move".to_string(),
             });
         }
+        *last_played = Instant::now();
         
         // Check if the game is over after player's move
         let is_game_over = game.game.is_someone_winning().is_some() || game.game.is_draw();
         let mut last_move = None;
+        // The game may have moved on while the solver was thinking, the check is only good for this position
+        if hopeless.is_some_and(|(hopeless, position)| hopeless && position.get_moves() == game.game.get_moves()) {
+            game.resigned = Some(*game.game.get_turn());
+        }
         // If game is not over, let AI make a move
-        if !is_game_over {
-            // Use the provided difficulty or default to game's default
-            let difficulty = req.ai_difficulty.unwrap_or(game.difficulty);
-            if difficulty < 0 {
-                return HttpResponse::BadRequest().json(ErrorResponse {
-                    error: "Invalid AI difficulty level, must be positive".to_string(),
-                });
+        if !is_game_over && difficulty != Some(0) && game.resigned.is_none() {
+            if let Some(engine) = one_off.as_deref_mut().or(game.ai.as_deref_mut()) {
+                last_move = Some(game.game.play_engine(engine, &limits));
             }
+        }
+        game.drop_engine_if_over();
 
+        return HttpResponse::Ok().json(game_to_response(game, &id, last_move));
+    } 
+    
+    HttpResponse::NotFound().json(ErrorResponse {
+        error: "Game not found".to_string(),
+    })
+}
 
+// The player to move resigns
+#[post("/games/{id}/resign")]
+async fn resign_game(path: web::Path<String>) -> HttpResponse {
+    let id = path.into_inner();
 
-            if difficulty != 0 {
-                last_move = Some(game.game.play_minimax(difficulty));
-            }
+    let mut games = GAMES.lock().unwrap();
+    if let Some((_, game)) = games.get_mut(&id) {
+        if game.termination().is_some() {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: "Game is already over".to_string(),
+            });
         }
+        game.resigned = Some(*game.game.get_turn());
+        game.drop_engine_if_over();
+        return HttpResponse::Ok().json(game_to_response(game, &id, None));
+    }
 
-        return HttpResponse::Ok().json(game_to_response(&game.game, &id, last_move));
-    } 
-    
     HttpResponse::NotFound().json(ErrorResponse {
         error: "Game not found".to_string(),
     })
 }
 
+// The player to move offers a draw, the AI accepts dead-drawn positions
+#[post("/games/{id}/draw")]
+async fn offer_draw(path: web::Path<String>) -> HttpResponse {
+    let id = path.into_inner();
+
+    let game = match GAMES.lock().unwrap().get(&id) {
+        Some((_, game)) if game.termination().is_some() => {
+            return HttpResponse::BadRequest().json(ErrorResponse {
+                error: "Game is already over".to_string(),
+            });
+        }
+        Some((_, game)) => game.game.clone(),
+        None => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Game not found".to_string(),
+            });
+        }
+    };
+    // Solving can take a while, keep it off the async workers and the games lock
+    let Ok((game, accepted)) = web::block(move || {
+        let accepted = is_dead_draw(&game);
+        (game, accepted)
+    })
+    .await
+    else {
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Draw offer failed".to_string(),
+        });
+    };
+
+    let mut games = GAMES.lock().unwrap();
+    match games.get_mut(&id) {
+        // The game may have moved on while the solver was thinking
+        Some((_, stored)) if stored.game.get_moves() == game.get_moves() && stored.termination().is_none() => {
+            stored.draw_agreed = accepted;
+            stored.drop_engine_if_over();
+            HttpResponse::Ok().json(DrawResponse { accepted, game: game_to_response(stored, &id, None) })
+        }
+        Some(_) => HttpResponse::Conflict().json(ErrorResponse {
+            error: "Game changed during the draw offer".to_string(),
+        }),
+        None => HttpResponse::NotFound().json(ErrorResponse {
+            error: "Game not found".to_string(),
+        }),
+    }
+}
+
 // Delete a game
 #[delete("/games/{id}")]
 async fn delete_game(path: web::Path<String>) -> HttpResponse {
@@ -197,21 +612,221 @@ async fn delete_game(path: web::Path<String>) -> HttpResponse {
     }
 }
 
+// Deepest evaluation a client can ask for
+const MAX_EVALUATE_DEPTH: i32 = 12;
+
+// Search depth of an evaluation, within the server's limits
+fn evaluate_depth(req: &EvaluateRequest) -> Result<i32, String> {
+    if !(1..=MAX_EVALUATE_DEPTH).contains(&req.depth) {
+        return Err(format!("Evaluation depth must be between 1 and {}", MAX_EVALUATE_DEPTH));
+    }
+    Ok(req.depth)
+}
+
 // Get evaluation of the game state
 #[get("/games/{id}/evaluate")]
 async fn evaluate_game(path: web::Path<String>, req: web::Query<EvaluateRequest>) -> HttpResponse {
     let id = path.into_inner();
-    
-    let mut games = GAMES.lock().unwrap();
-    if let Some(game) = games.get_mut(&id) {
-        let depth = req.depth;
-        let evaluation = evaluate_position(&mut game.game, depth);
-        return HttpResponse::Ok().json(evaluation);
+
+    let mut game = match GAMES.lock().unwrap().get(&id) {
+        Some((_, game)) => game.game.clone(),
+        None => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Game not found".to_string(),
+            });
+        }
+    };
+    let depth = match evaluate_depth(&req) {
+        Ok(depth) => depth,
+        Err(error) => return HttpResponse::BadRequest().json(ErrorResponse { error }),
+    };
+    let moves = game.get_moves().len();
+    // Searching takes a while, keep it off the async workers and the games lock
+    // A one-off search, a small table does and keeps the request cheap
+    match web::block(move || evaluate_position(&mut game, depth, &TranspositionTable::new(1))).await {
+        Ok(score) => {
+            let plies_to_win = plies_to_win(score, moves);
+            HttpResponse::Ok().json(EvaluateResponse { score, plies_to_win, wdl: WDL.wdl(score) })
+        }
+        Err(_) => HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Evaluation failed".to_string(),
+        }),
     }
-    
-    HttpResponse::NotFound().json(ErrorResponse {
-        error: "Game not found".to_string(),
+}
+
+// Deepest hint a client can ask for
+const MAX_HINT_DEPTH: i32 = 12;
+
+// Suggest a move for the player to move
+#[get("/games/{id}/hint")]
+async fn hint_game(path: web::Path<String>, req: web::Query<HintRequest>) -> HttpResponse {
+    let id = path.into_inner();
+
+    let game = match GAMES.lock().unwrap().get(&id) {
+        Some((_, game)) => game.game.clone(),
+        None => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Game not found".to_string(),
+            });
+        }
+    };
+    let depth = req.depth.unwrap_or(DEFAULT_HINT_DEPTH);
+    if !(1..=MAX_HINT_DEPTH).contains(&depth) {
+        return HttpResponse::BadRequest().json(ErrorResponse {
+            error: format!("Hint depth must be between 1 and {}", MAX_HINT_DEPTH),
+        });
+    }
+    let limits = SearchLimits {
+        depth,
+        time: req.time_ms.map(Duration::from_millis),
+        nodes: None,
+    };
+    match web::block(move || hint(&game, &limits)).await {
+        Ok(Some(hint)) => HttpResponse::Ok().json(hint),
+        Ok(None) => HttpResponse::BadRequest().json(ErrorResponse {
+            error: "Game is over".to_string(),
+        }),
+        Err(_) => HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Hint failed".to_string(),
+        }),
+    }
+}
+
+// Largest budget a review can ask for, every move of the game gets it
+const MAX_REVIEW_DEPTH: i32 = 12;
+const MAX_REVIEW_NODES: u64 = 2_000_000;
+
+// Search depth and solver nodes of a review, within the server's limits
+fn review_limits(req: &ReviewRequest) -> Result<(i32, u64), String> {
+    let depth = req.depth.unwrap_or(DEFAULT_REVIEW_DEPTH);
+    if !(1..=MAX_REVIEW_DEPTH).contains(&depth) {
+        return Err(format!("Review depth must be between 1 and {}", MAX_REVIEW_DEPTH));
+    }
+    let nodes = req.solver_nodes.unwrap_or(DEFAULT_REVIEW_NODES);
+    if nodes > MAX_REVIEW_NODES {
+        return Err(format!("Review solver nodes must be at most {}", MAX_REVIEW_NODES));
+    }
+    Ok((depth, nodes))
+}
+
+// Score every move of the game and summarize each player's accuracy
+#[get("/games/{id}/review")]
+async fn review_game(path: web::Path<String>, req: web::Query<ReviewRequest>) -> HttpResponse {
+    let id = path.into_inner();
+
+    let game = match GAMES.lock().unwrap().get(&id) {
+        Some((_, game)) => game.game.clone(),
+        None => {
+            return HttpResponse::NotFound().json(ErrorResponse {
+                error: "Game not found".to_string(),
+            });
+        }
+    };
+    let (depth, nodes) = match review_limits(&req) {
+        Ok(limits) => limits,
+        Err(error) => return HttpResponse::BadRequest().json(ErrorResponse { error }),
+    };
+    // Solving every position takes a while, keep it off the async workers and the games lock
+    match web::block(move || review(&game, depth, nodes)).await {
+        Ok(review) => HttpResponse::Ok().json(review),
+        Err(_) => HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Review failed".to_string(),
+        }),
+    }
+}
+
+// Keep `value` under `id`, dropping the entries older than `ttl`, and the oldest one once there are `limit`
+fn store_expiring<T>(
+    entries: &mut HashMap<String, (Instant, T)>,
+    id: String,
+    started: Instant,
+    value: T,
+    ttl: Duration,
+    limit: usize,
+) {
+    entries.retain(|_, (started, _)| started.elapsed() < ttl);
+    if entries.len() >= limit {
+        let oldest = entries.iter().min_by_key(|(_, (started, _))| *started).map(|(id, _)| id.clone());
+        if let Some(oldest) = oldest {
+            entries.remove(&oldest);
+        }
+    }
+    entries.insert(id, (started, value));
+}
+
+// Start a puzzle, the engine answers each move sent to POST /puzzles/{id}/attempt
+#[get("/puzzles/next")]
+async fn next_puzzle(req: web::Query<NextPuzzleRequest>) -> HttpResponse {
+    // The first request may have to mine the puzzles
+    let puzzles = match web::block(|| Lazy::force(&PUZZLES)).await {
+        Ok(Ok(puzzles)) => puzzles,
+        Ok(Err(error)) => return HttpResponse::InternalServerError().json(ErrorResponse { error: error.clone() }),
+        Err(_) => {
+            return HttpResponse::InternalServerError().json(ErrorResponse {
+                error: "Loading puzzles failed".to_string(),
+            });
+        }
+    };
+    let puzzle = match req.rating {
+        Some(rating) => puzzles.closest(rating),
+        None => puzzles.puzzles.choose(&mut rand::thread_rng()),
+    };
+    let Some(puzzle) = puzzle else {
+        return HttpResponse::NotFound().json(ErrorResponse {
+            error: "No puzzles".to_string(),
+        });
+    };
+
+    let id = Uuid::new_v4().to_string();
+    let attempt = PuzzleAttempt::new(puzzle);
+    let response = PuzzleResponse {
+        id: id.clone(),
+        puzzle: puzzle.id.clone(),
+        board: board_response(&attempt.game),
+        moves: attempt.game.get_moves().to_vec(),
+        player: attempt.player,
+        win_in: puzzle.win_in,
+        rating: puzzle.rating,
+    };
+    store_expiring(&mut ATTEMPTS.lock().unwrap(), id, Instant::now(), attempt, ATTEMPT_TTL, MAX_ATTEMPTS);
+    HttpResponse::Ok().json(response)
+}
+
+// Play a move of a puzzle, answered by the engine's defence while the puzzle goes on
+#[post("/puzzles/{id}/attempt")]
+async fn attempt_puzzle(path: web::Path<String>, req: web::Json<AttemptRequest>) -> HttpResponse {
+    let id = path.into_inner();
+
+    let Some((started, mut attempt)) = ATTEMPTS.lock().unwrap().remove(&id) else {
+        return HttpResponse::NotFound().json(ErrorResponse {
+            error: "Puzzle not found".to_string(),
+        });
+    };
+    let column = req.column;
+    // Checking the move takes the solver, keep it off the async workers and the attempts lock
+    let Ok((attempt, step)) = web::block(move || {
+        let step = attempt.play(column, &mut PUZZLE_SOLVER.lock().unwrap());
+        (attempt, step)
     })
+    .await
+    else {
+        return HttpResponse::InternalServerError().json(ErrorResponse {
+            error: "Attempt failed".to_string(),
+        });
+    };
+
+    let response = step.map(|step| AttemptResponse {
+        step,
+        board: board_response(&attempt.game),
+        moves: attempt.game.get_moves().to_vec(),
+    });
+    if !attempt.is_finished() {
+        store_expiring(&mut ATTEMPTS.lock().unwrap(), id, started, attempt, ATTEMPT_TTL, MAX_ATTEMPTS);
+    }
+    match response {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(error) => HttpResponse::BadRequest().json(ErrorResponse { error }),
+    }
 }
 
 pub async fn run_server() -> std::io::Result<()> {
@@ -234,7 +849,13 @@ pub async fn run_server() -> std::io::Result<()> {
             .service(list_games)
             .service(make_move)
             .service(delete_game)
+            .service(resign_game)
+            .service(offer_draw)
             .service(evaluate_game)
+            .service(hint_game)
+            .service(review_game)
+            .service(next_puzzle)
+            .service(attempt_puzzle)
     })
     .bind("0.0.0.0:8080") {
         Ok(server) => {
@@ -252,4 +873,157 @@ pub async fn run_server() -> std::io::Result<()> {
             Err(e)
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::position;
+    use crate::puzzle::Puzzle;
+
+    #[test]
+    fn test_client_engine_names_no_files() {
+        assert!(client_engine("minimax:depth=4,hash=64").is_ok());
+        let path = std::env::temp_dir().join("connect4_test_client.db");
+        let _ = std::fs::remove_file(&path);
+        let db = format!("minimax:depth=4,db={}", path.display());
+        for spec in [&db[..], "minimax:book=book.json", "greedy:eval=/etc/passwd", "minimax:verbose=true", "minimax:ponder=true"] {
+            assert!(client_engine(spec).is_err(), "{}", spec);
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_client_engine_limits() {
+        assert!(client_engine("minimax").is_ok());
+        assert!(client_engine("minimax:hash=64,threads=4").is_ok());
+        assert!(client_engine("minimax:hash=1000000").is_err());
+        assert!(client_engine("minimax:threads=100000").is_err());
+        assert!(client_engine("mcts:nodes=1000000000").is_err());
+        assert!(client_engine("minimax:depth=12,time=2000").is_ok());
+        assert!(client_engine("minimax:depth=13").is_err());
+        assert!(client_engine("mcts:time=0").is_err());
+        let too_much = [
+            r#"{ "hash_mb": 1000000 }"#,
+            r#"{ "depth": 100 }"#,
+            r#"{ "engine": "mcts", "time_ms": 100000000 }"#,
+            r#"{ "ponder": true }"#,
+        ];
+        for json in too_much {
+            let config: EngineConfig = serde_json::from_str(json).unwrap();
+            assert!(check_client_engine(&config).is_err(), "{}", json);
+        }
+    }
+
+    fn stored(moves: &str) -> GameAndDifficulty {
+        GameAndDifficulty {
+            game: position(moves),
+            difficulty: None,
+            config: EngineConfig::new("random"),
+            seed: 0,
+            ai: Some(EngineConfig::new("random").build().unwrap()),
+            ai_resigns: true,
+            resigned: None,
+            draw_agreed: false,
+        }
+    }
+
+    // Lost for red, to move, in 4 plies
+    const LOST_IN_4: &str = "023611540024663423";
+    // Lost for red, to move, in 8 plies
+    const LOST_IN_8: &str = "3542230230036642463436216025";
+    // A draw with perfect play, yellow to move
+    const DEAD_DRAW: &str = "10526201430510512";
+
+    #[test]
+    fn test_termination() {
+        let mut game = stored("33");
+        assert_eq!((game.termination(), game.winner()), (None, None));
+        game.resigned = Some(Player::Red);
+        assert_eq!((game.termination(), game.winner()), (Some(Termination::Resignation), Some(Player::Yellow)));
+
+        let mut game = stored("33");
+        game.draw_agreed = true;
+        assert_eq!((game.termination(), game.winner()), (Some(Termination::DrawAgreed), None));
+
+        let mut game = stored("0101010");
+        assert_eq!((game.termination(), game.winner()), (Some(Termination::FourInARow), Some(Player::Red)));
+
+        // Only a finished game lets go of its engine
+        game.drop_engine_if_over();
+        assert!(game.ai.is_none());
+        let mut game = stored("33");
+        game.drop_engine_if_over();
+        assert!(game.ai.is_some());
+    }
+
+    #[test]
+    fn test_resignation() {
+        assert!(is_hopeless(&position(LOST_IN_4)));
+        // Lost, but further away than `RESIGN_PLIES`
+        assert!(!is_hopeless(&position(LOST_IN_8)));
+        assert!(!is_hopeless(&position(DEAD_DRAW)));
+
+        // Only a game the AI answers is checked
+        let move_request = |column, ai_difficulty| MoveRequest { column, ai_difficulty, ai_engine: None, seed: None };
+        let (moves, last) = LOST_IN_4.split_at(17);
+        let game = stored(moves);
+        let checked = resign_check(&game, &move_request(last.parse().unwrap(), None)).unwrap();
+        assert_eq!(checked.get_moves(), position(LOST_IN_4).get_moves());
+        assert!(resign_check(&game, &move_request(last.parse().unwrap(), Some(0))).is_none());
+        assert!(resign_check(&stored("010101"), &move_request(0, None)).is_none());
+        assert!(resign_check(&GameAndDifficulty { ai_resigns: false, ..stored("") }, &move_request(3, None)).is_none());
+    }
+
+    #[test]
+    fn test_draw_acceptance() {
+        assert!(is_dead_draw(&position(DEAD_DRAW)));
+        assert!(!is_dead_draw(&position(LOST_IN_4)));
+    }
+
+    #[test]
+    fn test_attempts_expire() {
+        let puzzle = Puzzle { id: "0".to_string(), moves: vec![2, 0, 3, 6], win_in: 2, solution: 4, rating: 1000 };
+        let now = Instant::now();
+        let mut attempts = HashMap::new();
+        let expired = now.checked_sub(ATTEMPT_TTL + Duration::from_secs(1)).unwrap();
+        let store = |attempts: &mut HashMap<_, _>, id: &str, started| {
+            store_expiring(attempts, id.to_string(), started, PuzzleAttempt::new(&puzzle), ATTEMPT_TTL, 2)
+        };
+        store(&mut attempts, "old", expired);
+        store(&mut attempts, "a", now);
+        assert!(!attempts.contains_key("old"));
+        // At the limit the oldest attempt makes room
+        store(&mut attempts, "b", now + Duration::from_secs(1));
+        store(&mut attempts, "c", now + Duration::from_secs(2));
+        let mut ids: Vec<&String> = attempts.keys().collect();
+        ids.sort();
+        assert_eq!(ids, ["b", "c"]);
+    }
+
+    #[test]
+    fn test_review_limits() {
+        let limits = |depth, solver_nodes| review_limits(&ReviewRequest { depth, solver_nodes });
+        assert_eq!(limits(None, None), Ok((DEFAULT_REVIEW_DEPTH, DEFAULT_REVIEW_NODES)));
+        assert_eq!(limits(Some(1), Some(0)), Ok((1, 0)));
+        assert!(limits(Some(0), None).is_err());
+        assert!(limits(Some(MAX_REVIEW_DEPTH + 1), None).is_err());
+        assert!(limits(None, Some(u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_depth() {
+        assert_eq!(evaluate_depth(&EvaluateRequest { depth: 1 }), Ok(1));
+        assert_eq!(evaluate_depth(&EvaluateRequest { depth: MAX_EVALUATE_DEPTH }), Ok(MAX_EVALUATE_DEPTH));
+        assert!(evaluate_depth(&EvaluateRequest { depth: 0 }).is_err());
+        assert!(evaluate_depth(&EvaluateRequest { depth: MAX_EVALUATE_DEPTH + 1 }).is_err());
+    }
+
+    #[test]
+    fn test_difficulty_limits() {
+        assert!(check_difficulty(None).is_ok());
+        assert!(check_difficulty(Some(0)).is_ok());
+        assert!(check_difficulty(Some(MAX_CLIENT_DEPTH)).is_ok());
+        assert!(check_difficulty(Some(-1)).is_err());
+        assert!(check_difficulty(Some(MAX_CLIENT_DEPTH + 1)).is_err());
+    }
+}
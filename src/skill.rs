@@ -0,0 +1,225 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::engine::{Engine, EngineConfig, SearchLimits, SearchResult};
+use crate::evaluator::{self, EvalParams};
+use crate::solver::Position;
+use crate::tournament::{self, Format, TournamentConfig};
+use crate::transposition::{TranspositionTable, DEFAULT_HASH_MB};
+use crate::Connect4;
+
+/// Named difficulty levels, from beginner to master
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SkillLevel {
+    Beginner,
+    Novice,
+    Intermediate,
+    Advanced,
+    Expert,
+    Master,
+}
+
+/// How a skill level picks its moves
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SkillParams {
+    /// Search depth used to score the moves
+    pub depth: i32,
+    /// Softmax temperature over move scores, 0 always plays the best move
+    pub temperature: f64,
+    /// Chance of playing any legal move instead
+    pub blunder_rate: f64,
+    /// Approximate Elo, 0 being the random mover
+    pub elo: i32,
+}
+
+impl SkillLevel {
+    pub const ALL: [SkillLevel; 6] = [
+        SkillLevel::Beginner,
+        SkillLevel::Novice,
+        SkillLevel::Intermediate,
+        SkillLevel::Advanced,
+        SkillLevel::Expert,
+        SkillLevel::Master,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SkillLevel::Beginner => "beginner",
+            SkillLevel::Novice => "novice",
+            SkillLevel::Intermediate => "intermediate",
+            SkillLevel::Advanced => "advanced",
+            SkillLevel::Expert => "expert",
+            SkillLevel::Master => "master",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SkillLevel> {
+        SkillLevel::ALL.into_iter().find(|level| level.name() == name)
+    }
+
+    /// Elo values come from `calibrate`, run as `connect4 calibrate --seed 1`, 14 games per pairing
+    /// Rerun it after changing the evaluation or the search
+    pub fn params(&self) -> SkillParams {
+        match self {
+            SkillLevel::Beginner => SkillParams { depth: 1, temperature: 30.0, blunder_rate: 0.3, elo: 350 },
+            SkillLevel::Novice => SkillParams { depth: 2, temperature: 15.0, blunder_rate: 0.15, elo: 540 },
+            SkillLevel::Intermediate => SkillParams { depth: 4, temperature: 8.0, blunder_rate: 0.08, elo: 650 },
+            SkillLevel::Advanced => SkillParams { depth: 6, temperature: 4.0, blunder_rate: 0.04, elo: 760 },
+            SkillLevel::Expert => SkillParams { depth: 8, temperature: 1.5, blunder_rate: 0.01, elo: 890 },
+            SkillLevel::Master => SkillParams { depth: 10, temperature: 0.0, blunder_rate: 0.0, elo: 1080 },
+        }
+    }
+}
+
+/// Round robin of every level and the random engine over all openings of `opening_plies` plies
+/// Returns the Elo of every level, the random engine being rated 0
+pub fn calibrate(opening_plies: u32, threads: usize, seed: u64) -> Vec<(SkillLevel, f64)> {
+    let mut participants = vec![EngineConfig::new("random")];
+    participants.extend(SkillLevel::ALL.iter().map(|level| EngineConfig::new(level.name())));
+    let config = TournamentConfig {
+        participants,
+        format: Format::RoundRobin,
+        opening_plies,
+        threads,
+        sprt: None,
+        seed,
+    };
+    let pairings = tournament::run_tournament(&config);
+    let ratings = tournament::fit_ratings(config.participants.len(), &pairings);
+    SkillLevel::ALL.into_iter().zip(ratings.into_iter().skip(1)).collect()
+}
+
+/// Plays like a human of the given level: an immediate win or a forced block is never missed,
+/// otherwise the move is drawn from a softmax over search scores, with the odd blunder
+pub struct SkillEngine {
+    pub level: SkillLevel,
+    pub eval: EvalParams,
+    rng: StdRng,
+    table: TranspositionTable,
+}
+
+impl SkillEngine {
+    pub fn new(level: SkillLevel, seed: u64) -> SkillEngine {
+        SkillEngine::with_hash(level, seed, DEFAULT_HASH_MB)
+    }
+
+    /// Engine with a transposition table of `hash_mb` megabytes
+    pub fn with_hash(level: SkillLevel, seed: u64, hash_mb: usize) -> SkillEngine {
+        SkillEngine {
+            level,
+            eval: EvalParams::default(),
+            rng: StdRng::seed_from_u64(seed),
+            table: TranspositionTable::new(hash_mb),
+        }
+    }
+}
+
+impl Engine for SkillEngine {
+    fn name(&self) -> &'static str {
+        self.level.name()
+    }
+
+    fn choose_move(&mut self, game: &Connect4, _limits: &SearchLimits) -> SearchResult {
+        let params = self.level.params();
+
+        let position = Position::from_game(game);
+        if let Some(&col) = position.winning_columns().first() {
+            return SearchResult {
+                best_move: col,
+                score: evaluator::WIN_SCORE - game.get_moves().len() as i32 - 1,
+                depth: 1,
+                nodes: 0,
+            };
+        }
+        if let Some(&col) = position.threatened_columns().first() {
+            return SearchResult {
+                best_move: col,
+                score: 0,
+                depth: 1,
+                nodes: 0,
+            };
+        }
+
+        let mut game = game.clone();
+        let scores = evaluator::score_moves(&mut game, params.depth, &self.table, &self.eval);
+        let best_score = scores.iter().map(|&(_, score)| score).max().unwrap();
+
+        let best_move = if self.rng.gen_bool(params.blunder_rate) {
+            scores.choose(&mut self.rng).unwrap().0
+        } else {
+            // Temperatures were calibrated with every win scored 100
+            let scores: Vec<(u32, i32)> = scores.iter().map(|&(col, score)| (col, score.clamp(-100, 100))).collect();
+            pick_softmax(&scores, params.temperature, &mut self.rng)
+        };
+        SearchResult {
+            best_move,
+            score: best_score,
+            depth: params.depth,
+            nodes: 0,
+        }
+    }
+}
+
+/// Pick a move with probability proportional to exp(score / temperature)
+fn pick_softmax(scores: &[(u32, i32)], temperature: f64, rng: &mut impl Rng) -> u32 {
+    let best = scores.iter().max_by_key(|&&(_, score)| score).unwrap();
+    if temperature <= 0.0 {
+        return best.0;
+    }
+    let weights = scores
+        .iter()
+        .map(|&(_, score)| ((score - best.1) as f64 / temperature).exp());
+    let distribution = WeightedIndex::new(weights).unwrap();
+    scores[distribution.sample(rng)].0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_names() {
+        for level in SkillLevel::ALL {
+            assert_eq!(SkillLevel::from_name(level.name()), Some(level));
+        }
+        assert_eq!(SkillLevel::from_name("grandmaster"), None);
+    }
+
+    #[test]
+    fn test_elo_increases_with_level() {
+        let elos: Vec<i32> = SkillLevel::ALL.iter().map(|level| level.params().elo).collect();
+        assert!(elos[0] > 0, "{:?}", elos);
+        assert!(elos.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", elos);
+    }
+
+    #[test]
+    fn test_never_misses_win_or_block() {
+        let mut win = Connect4::new();
+        for col in [0, 1, 0, 1, 0, 1] {
+            win.play(col);
+        }
+        let mut block = Connect4::new();
+        for col in [0, 1, 0, 1, 0] {
+            block.play(col);
+        }
+
+        let mut engine = SkillEngine::new(SkillLevel::Beginner, 0);
+        for _ in 0..20 {
+            assert_eq!(engine.choose_move(&win, &SearchLimits::depth(1)).best_move, 0);
+            assert_eq!(engine.choose_move(&block, &SearchLimits::depth(1)).best_move, 0);
+        }
+    }
+
+    #[test]
+    fn test_softmax() {
+        let scores = [(3, 10), (2, -50), (4, 0)];
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(pick_softmax(&scores, 0.0, &mut rng), 3);
+        // A huge gap makes the worst move practically impossible
+        for _ in 0..100 {
+            assert_ne!(pick_softmax(&scores, 1.0, &mut rng), 2);
+        }
+    }
+}
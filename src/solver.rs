@@ -0,0 +1,601 @@
+use std::sync::Arc;
+
+use crate::database::{PositionDb, SolvedPosition};
+use crate::engine::WIN_SCORE;
+use crate::player::Player;
+use crate::{CellState, Connect4};
+
+// Bitboard layout: one 7-bit column after another, the top bit of each column is a sentinel
+const WIDTH: u32 = 7;
+const HEIGHT: u32 = 6;
+pub(crate) const CELLS: i32 = (WIDTH * HEIGHT) as i32;
+const MIN_SCORE: i32 = -CELLS / 2 + 3;
+const MAX_SCORE: i32 = (CELLS + 1) / 2 - 3;
+const BOTTOM_MASK: u64 = bottom_mask();
+const BOARD_MASK: u64 = BOTTOM_MASK * ((1 << HEIGHT) - 1);
+/// Rows 1, 3 and 5 counting from the bottom, where the first player wants their threats
+pub(crate) const ODD_ROWS: u64 = BOTTOM_MASK * 0b010101;
+/// Rows 2, 4 and 6, where the second player wants their threats
+pub(crate) const EVEN_ROWS: u64 = BOTTOM_MASK * 0b101010;
+const COLUMN_ORDER: [u32; 7] = [3, 2, 4, 1, 5, 0, 6];
+
+const fn bottom_mask() -> u64 {
+    let mut mask = 0;
+    let mut col = 0;
+    while col < WIDTH {
+        mask |= 1 << (col * (HEIGHT + 1));
+        col += 1;
+    }
+    mask
+}
+
+pub(crate) fn column_mask(col: u32) -> u64 {
+    ((1 << HEIGHT) - 1) << (col * (HEIGHT + 1))
+}
+
+fn top_mask(col: u32) -> u64 {
+    1 << (HEIGHT - 1 + col * (HEIGHT + 1))
+}
+
+fn bottom_mask_col(col: u32) -> u64 {
+    1 << (col * (HEIGHT + 1))
+}
+
+/// Cells that would complete an alignment of four for the stones in `position`
+fn winning_cells(position: u64, mask: u64) -> u64 {
+    // Vertical
+    let mut r = (position << 1) & (position << 2) & (position << 3);
+
+    // Horizontal and both diagonals
+    for shift in [HEIGHT + 1, HEIGHT, HEIGHT + 2] {
+        let mut p = (position << shift) & (position << (2 * shift));
+        r |= p & (position << (3 * shift));
+        r |= p & (position >> shift);
+        p = (position >> shift) & (position >> (2 * shift));
+        r |= p & (position << shift);
+        r |= p & (position >> (3 * shift));
+    }
+
+    r & (BOARD_MASK ^ mask)
+}
+
+/// Compact board used by the solver
+#[derive(Clone, Copy)]
+pub(crate) struct Position {
+    current: u64, // Stones of the player to move
+    mask: u64,    // All stones
+    pub(crate) moves: i32,
+}
+
+impl Position {
+    pub(crate) fn from_game(game: &Connect4) -> Position {
+        let to_move = game.get_turn().to_cell_state();
+        let board = game.get_board();
+        let mut position = Position { current: 0, mask: 0, moves: 0 };
+        for (row, cells) in board.iter().enumerate() {
+            for (col, &cell) in cells.iter().enumerate() {
+                if cell != CellState::Empty {
+                    let bit = 1 << (col as u32 * (HEIGHT + 1) + row as u32);
+                    position.mask |= bit;
+                    position.moves += 1;
+                    if cell == to_move {
+                        position.current |= bit;
+                    }
+                }
+            }
+        }
+        position
+    }
+
+    pub(crate) fn can_play(&self, col: u32) -> bool {
+        self.mask & top_mask(col) == 0
+    }
+
+    pub(crate) fn play(&mut self, col: u32) {
+        self.play_move((self.mask + bottom_mask_col(col)) & column_mask(col));
+    }
+
+    fn play_move(&mut self, mv: u64) {
+        self.current ^= self.mask;
+        self.mask |= mv;
+        self.moves += 1;
+    }
+
+    pub(crate) fn is_winning_move(&self, col: u32) -> bool {
+        self.winning_position() & self.possible() & column_mask(col) != 0
+    }
+
+    fn can_win_next(&self) -> bool {
+        self.winning_position() & self.possible() != 0
+    }
+
+    /// Columns where the player to move connects four right away
+    pub(crate) fn winning_columns(&self) -> Vec<u32> {
+        let winning = self.winning_position() & self.possible();
+        (0..WIDTH).filter(|&col| winning & column_mask(col) != 0).collect()
+    }
+
+    /// Columns where the opponent would connect four on their next move
+    pub(crate) fn threatened_columns(&self) -> Vec<u32> {
+        let threats = self.opponent_winning_position() & self.possible();
+        (0..WIDTH).filter(|&col| threats & column_mask(col) != 0).collect()
+    }
+
+    pub(crate) fn possible(&self) -> u64 {
+        (self.mask + BOTTOM_MASK) & BOARD_MASK
+    }
+
+    /// Empty cells completing four for the player to move, playable or not
+    pub(crate) fn winning_position(&self) -> u64 {
+        winning_cells(self.current, self.mask)
+    }
+
+    /// Empty cells completing four for the opponent, playable or not
+    pub(crate) fn opponent_winning_position(&self) -> u64 {
+        winning_cells(self.current ^ self.mask, self.mask)
+    }
+
+    /// Moves that do not give the opponent an immediate win
+    pub(crate) fn possible_non_losing_moves(&self) -> u64 {
+        let mut possible = self.possible();
+        let opponent_win = self.opponent_winning_position();
+        let forced = possible & opponent_win;
+        if forced != 0 {
+            if forced & (forced - 1) != 0 {
+                // Two threats at once, nothing can be done
+                return 0;
+            }
+            possible = forced;
+        }
+        // Avoid playing right below an opponent's winning cell
+        possible & !(opponent_win >> 1)
+    }
+
+    fn move_score(&self, mv: u64) -> u32 {
+        winning_cells(self.current | mv, self.mask).count_ones()
+    }
+
+    fn key(&self) -> u64 {
+        self.current + self.mask
+    }
+}
+
+/// Exact solver, a negamax with alpha-beta pruning over bitboards
+/// Scores are positive when the player to move wins: the earlier the win, the higher the score
+/// A win with the player's last stone is worth 1, a draw is 0
+pub struct Solver {
+    table: Vec<u64>,
+    nodes: u64,
+    node_limit: u64,
+    aborted: bool,
+    database: Option<Arc<PositionDb>>,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Solver {
+    pub fn new() -> Solver {
+        Solver {
+            table: vec![0; 1 << 21],
+            nodes: 0,
+            node_limit: u64::MAX,
+            aborted: false,
+            database: None,
+        }
+    }
+
+    /// Look solved positions up in `database` and add the ones solved here
+    pub fn set_database(&mut self, database: Arc<PositionDb>) {
+        self.database = Some(database);
+    }
+
+    /// Number of positions explored since the solver was created
+    pub fn get_nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Exact score of the game for the player to move
+    pub fn solve(&mut self, game: &Connect4) -> i32 {
+        if let Some(score) = terminal_score(game) {
+            return score;
+        }
+        if let Some(solved) = self.database.as_ref().and_then(|database| database.lookup(game)) {
+            return solved.score;
+        }
+        let score = self.solve_position(&Position::from_game(game));
+        self.record(game, score, None);
+        score
+    }
+
+    /// Same as `solve`, giving up once `max_nodes` positions have been explored
+    /// Early positions can take minutes to solve, this keeps the solver usable anywhere
+    pub fn try_solve(&mut self, game: &Connect4, max_nodes: u64) -> Option<i32> {
+        self.with_node_limit(max_nodes, |solver| solver.solve(game))
+    }
+
+    /// Same as `best_move`, giving up once `max_nodes` positions have been explored
+    pub fn try_best_move(&mut self, game: &Connect4, max_nodes: u64) -> Option<(u32, i32)> {
+        self.with_node_limit(max_nodes, |solver| solver.best_move(game)).flatten()
+    }
+
+    fn with_node_limit<T>(&mut self, max_nodes: u64, f: impl FnOnce(&mut Solver) -> T) -> Option<T> {
+        self.node_limit = self.nodes.saturating_add(max_nodes);
+        self.aborted = false;
+        let result = f(self);
+        self.node_limit = u64::MAX;
+        if std::mem::take(&mut self.aborted) {
+            None
+        } else {
+            Some(result)
+        }
+    }
+
+    /// Whether the player to move scores at least `score`, a single null window search
+    /// Much cheaper than `solve` when the answer is all that's needed, e.g. whether a win comes in time
+    pub fn score_at_least(&mut self, game: &Connect4, score: i32) -> bool {
+        if let Some(terminal) = terminal_score(game) {
+            return terminal >= score;
+        }
+        let position = Position::from_game(game);
+        if position.can_win_next() {
+            return (CELLS + 1 - position.moves) / 2 >= score;
+        }
+        self.negamax(&position, score - 1, score) >= score
+    }
+
+    /// Same as `score_at_least`, giving up once `max_nodes` positions have been explored
+    pub fn try_score_at_least(&mut self, game: &Connect4, score: i32, max_nodes: u64) -> Option<bool> {
+        self.with_node_limit(max_nodes, |solver| solver.score_at_least(game, score))
+    }
+
+    /// Exact score of each column for the player to move, `None` for full columns
+    pub fn analyze(&mut self, game: &Connect4) -> [Option<i32>; 7] {
+        let mut scores = [None; 7];
+        if terminal_score(game).is_some() {
+            return scores;
+        }
+        let position = Position::from_game(game);
+        for col in 0..WIDTH {
+            if position.can_play(col) {
+                scores[col as usize] = Some(if position.is_winning_move(col) {
+                    (CELLS + 1 - position.moves) / 2
+                } else {
+                    let mut next = position;
+                    next.play(col);
+                    -self.solve_position(&next)
+                });
+            }
+        }
+        scores
+    }
+
+    /// Best column for the player to move, preferring the center on equal scores
+    pub fn best_move(&mut self, game: &Connect4) -> Option<(u32, i32)> {
+        if terminal_score(game).is_some() {
+            return None;
+        }
+        let known = self.database.as_ref().and_then(|database| database.lookup(game));
+        if let Some(SolvedPosition { score, best_move: Some(col) }) = known {
+            return Some((col, score));
+        }
+        // No need to look any further than an immediate win
+        let position = Position::from_game(game);
+        for col in COLUMN_ORDER {
+            if position.can_play(col) && position.is_winning_move(col) {
+                return Some((col, (CELLS + 1 - position.moves) / 2));
+            }
+        }
+
+        let scores = self.analyze(game);
+        let mut best: Option<(u32, i32)> = None;
+        for col in COLUMN_ORDER {
+            if let Some(score) = scores[col as usize] {
+                if best.is_none_or(|(_, best_score)| score > best_score) {
+                    best = Some((col, score));
+                }
+            }
+        }
+        if let Some((col, score)) = best {
+            self.record(game, score, Some(col));
+        }
+        best
+    }
+
+    fn record(&self, game: &Connect4, score: i32, best_move: Option<u32>) {
+        if let (Some(database), false) = (&self.database, self.aborted) {
+            database.record(game, SolvedPosition { score, best_move });
+        }
+    }
+
+    fn solve_position(&mut self, position: &Position) -> i32 {
+        if position.can_win_next() {
+            return (CELLS + 1 - position.moves) / 2;
+        }
+        let mut min = -(CELLS - position.moves) / 2;
+        let mut max = (CELLS + 1 - position.moves) / 2;
+
+        // Narrow the score window with null window searches
+        while min < max && !self.aborted {
+            let mut med = min + (max - min) / 2;
+            if med <= 0 && min / 2 < med {
+                med = min / 2;
+            } else if med >= 0 && max / 2 > med {
+                med = max / 2;
+            }
+            let score = self.negamax(position, med, med + 1);
+            if score <= med {
+                max = score;
+            } else {
+                min = score;
+            }
+        }
+        min
+    }
+
+    fn negamax(&mut self, position: &Position, mut alpha: i32, mut beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.nodes >= self.node_limit {
+            self.aborted = true;
+        }
+        if self.aborted {
+            return 0;
+        }
+
+        let possible = position.possible_non_losing_moves();
+        if possible == 0 {
+            return -(CELLS - position.moves) / 2;
+        }
+        if position.moves >= CELLS - 2 {
+            return 0;
+        }
+
+        let min = -(CELLS - 2 - position.moves) / 2;
+        if alpha < min {
+            alpha = min;
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        let mut max = (CELLS - 1 - position.moves) / 2;
+        if let Some(value) = self.get(position.key()) {
+            if value > MAX_SCORE - MIN_SCORE + 1 {
+                let min = value + 2 * MIN_SCORE - MAX_SCORE - 2;
+                if alpha < min {
+                    alpha = min;
+                    if alpha >= beta {
+                        return alpha;
+                    }
+                }
+            } else {
+                max = value + MIN_SCORE - 1;
+            }
+        }
+        if beta > max {
+            beta = max;
+            if alpha >= beta {
+                return beta;
+            }
+        }
+
+        // Try the moves creating the most new threats first, center first on ties
+        let mut moves: Vec<(u64, u32)> = COLUMN_ORDER
+            .iter()
+            .map(|&col| possible & column_mask(col))
+            .filter(|&mv| mv != 0)
+            .map(|mv| (mv, position.move_score(mv)))
+            .collect();
+        moves.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+
+        for (mv, _) in moves {
+            let mut next = *position;
+            next.play_move(mv);
+            let score = -self.negamax(&next, -beta, -alpha);
+            if self.aborted {
+                // Scores are meaningless once aborted, keep them out of the table
+                return 0;
+            }
+            if score >= beta {
+                self.put(position.key(), score + MAX_SCORE - 2 * MIN_SCORE + 2);
+                return score;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        self.put(position.key(), alpha - MIN_SCORE + 1);
+        alpha
+    }
+
+    // Entries pack the full key with the stored value in the low byte
+    fn get(&self, key: u64) -> Option<i32> {
+        let entry = self.table[self.index(key)];
+        if entry != 0 && entry >> 8 == key {
+            Some((entry & 0xff) as i32)
+        } else {
+            None
+        }
+    }
+
+    fn put(&mut self, key: u64, value: i32) {
+        let index = self.index(key);
+        self.table[index] = (key << 8) | value as u64;
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 43) as usize & (self.table.len() - 1)
+    }
+}
+
+/// Score of a game that is already over, from the point of view of the player to move
+pub(crate) fn terminal_score(game: &Connect4) -> Option<i32> {
+    if game.is_someone_winning().is_some() {
+        // The previous player connected four with their last stone
+        let moves = game.get_moves().len() as i32;
+        return Some(-(CELLS + 2 - moves) / 2);
+    }
+    if game.is_draw() {
+        return Some(0);
+    }
+    None
+}
+
+/// Number of plies until the end of the game under perfect play, for a solver score
+/// `moves` is the number of stones already on the board
+pub fn plies_to_end(score: i32, moves: i32) -> i32 {
+    if score == 0 {
+        return CELLS - moves;
+    }
+    // The winner plays their winning stone as move number `last`
+    let winner_moved_first = (moves % 2 == 0) == (score > 0);
+    let last = if winner_moved_first {
+        CELLS + 1 - 2 * score.abs()
+    } else {
+        CELLS + 2 - 2 * score.abs()
+    };
+    last - moves
+}
+
+/// Solver score of a position with `moves` plies played, on the scale of the alpha-beta search
+/// A win scores `WIN_SCORE` minus the plies of the game when it happens
+pub fn search_score(score: i32, moves: i32) -> i32 {
+    if score == 0 {
+        return 0;
+    }
+    score.signum() * (WIN_SCORE - moves - plies_to_end(score, moves))
+}
+
+/// Inverse of `search_score`, for a decisive or drawn search score
+pub fn solver_score(score: i32) -> i32 {
+    if score == 0 {
+        return 0;
+    }
+    // The winner's stones, counting the winning one
+    let stones = (WIN_SCORE - score.abs() + 1) / 2;
+    score.signum() * (CELLS / 2 + 1 - stones)
+}
+
+/// Solver score of the player to move connecting four with their `win_in`-th next stone
+/// `moves` is the number of stones already on the board
+pub fn win_score(moves: i32, win_in: i32) -> i32 {
+    CELLS / 2 + 1 - moves / 2 - win_in
+}
+
+/// Whose stones win under perfect play, `None` for a draw
+pub fn winner(game: &Connect4, score: i32) -> Option<Player> {
+    let to_move = *game.get_turn();
+    match score.signum() {
+        1 => Some(to_move),
+        -1 => Some(to_move.opponent()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game_from(moves: &str) -> Connect4 {
+        let mut game = Connect4::new();
+        for c in moves.chars() {
+            assert!(game.play(c.to_digit(10).unwrap() - 1));
+        }
+        game
+    }
+
+    #[test]
+    fn test_solve_reference_positions() {
+        // Positions and scores from Pascal Pons' test set, columns numbered from 1
+        let mut solver = Solver::new();
+        assert_eq!(solver.solve(&game_from("2252576253462244111563365343671351441")), -1);
+        assert_eq!(solver.solve(&game_from("7422341735647741166133573473242566")), 1);
+        assert_eq!(solver.solve(&game_from("23163416124767223154467471272416755633")), 0);
+        assert_eq!(solver.solve(&game_from("65214673556155731566316327373221417")), -1);
+    }
+
+    #[test]
+    fn test_immediate_win() {
+        let mut game = Connect4::new();
+        for col in [0, 1, 0, 1, 0, 1] {
+            game.play(col);
+        }
+        let mut solver = Solver::new();
+        assert_eq!(solver.best_move(&game).unwrap().0, 0);
+        let score = solver.solve(&game);
+        assert_eq!(plies_to_end(score, 6), 1);
+        assert_eq!(winner(&game, score), Some(Player::Red));
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let mut solver = Solver::new();
+        assert_eq!(solver.try_solve(&Connect4::new(), 1000), None);
+        let game = game_from("7422341735647741166133573473242566");
+        assert_eq!(solver.try_solve(&game, 1_000_000), Some(1));
+    }
+
+    #[test]
+    fn test_already_won() {
+        let mut game = Connect4::new();
+        for col in [0, 1, 0, 1, 0, 1, 0] {
+            game.play(col);
+        }
+        let score = Solver::new().solve(&game);
+        assert!(score < 0);
+        assert_eq!(plies_to_end(score, 7), 0);
+    }
+
+    #[test]
+    fn test_database_skips_solved_positions() {
+        let database = Arc::new(PositionDb::in_memory());
+        let mut solver = Solver::new();
+        solver.set_database(database.clone());
+        // An aborted search proves nothing
+        assert_eq!(solver.try_solve(&Connect4::new(), 1000), None);
+        assert!(database.is_empty());
+
+        let game = game_from("7422341735647741166133573473242566");
+        let best = solver.best_move(&game).unwrap();
+        assert_eq!(database.lookup(&game), Some(SolvedPosition { score: 1, best_move: Some(best.0) }));
+
+        let mut fresh = Solver::new();
+        fresh.set_database(database);
+        assert_eq!(fresh.best_move(&game), Some(best));
+        assert_eq!(fresh.solve(&game), 1);
+        assert_eq!(fresh.get_nodes(), 0);
+    }
+
+    #[test]
+    fn test_score_at_least() {
+        let mut solver = Solver::new();
+        let mut game = Connect4::new();
+        for col in [0, 1, 0, 1, 0, 1] {
+            game.play(col);
+        }
+        assert_eq!(solver.solve(&game), win_score(6, 1));
+        assert!(solver.score_at_least(&game, win_score(6, 1)));
+        assert!(!solver.score_at_least(&game, win_score(6, 1) + 1));
+
+        // Red holds 2 and 3 on the bottom row, yellow the edges, 4 makes an open three
+        let mut game = Connect4::new();
+        for col in [2, 0, 3, 6] {
+            game.play(col);
+        }
+        assert!(solver.score_at_least(&game, win_score(4, 2)));
+        assert!(!solver.score_at_least(&game, win_score(4, 1)));
+    }
+
+    #[test]
+    fn test_solver_score_round_trip() {
+        for moves in 0..CELLS {
+            for score in [-(CELLS - moves) / 2, -1, 0, 1, (CELLS + 1 - moves) / 2] {
+                assert_eq!(solver_score(search_score(score, moves)), score);
+            }
+        }
+    }
+}
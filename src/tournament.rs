@@ -0,0 +1,356 @@
+use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::engine::{derive_seed, EngineConfig};
+use crate::player::Player;
+use crate::Connect4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Every participant plays every other one
+    RoundRobin,
+    /// The first participant plays every other one
+    Gauntlet,
+}
+
+/// Sequential probability ratio test between two Elo hypotheses
+#[derive(Clone, Copy, Debug)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64) -> Sprt {
+        Sprt { elo0, elo1, alpha: 0.05, beta: 0.05 }
+    }
+
+    /// Log-likelihood ratio bounds, (lower, upper)
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SprtDecision {
+    AcceptH0,
+    AcceptH1,
+    Continue,
+}
+
+pub struct TournamentConfig {
+    pub participants: Vec<EngineConfig>,
+    pub format: Format,
+    /// Number of plies of every opening, all openings of that length are played
+    pub opening_plies: u32,
+    pub threads: usize,
+    pub sprt: Option<Sprt>,
+    /// Every game gets its own seed derived from this one, whatever thread plays it
+    pub seed: u64,
+}
+
+/// Results of a pairing, from the point of view of its first participant
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MatchResult {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// Elo difference and the half width of its 95% confidence interval
+#[derive(Clone, Copy, Debug)]
+pub struct EloEstimate {
+    pub elo: f64,
+    pub error: f64,
+}
+
+impl MatchResult {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Average score per game, a draw being worth half a win
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    fn variance(&self) -> f64 {
+        let mean = self.score();
+        let games = self.games() as f64;
+        (self.wins as f64 * (1.0 - mean).powi(2)
+            + self.draws as f64 * (0.5 - mean).powi(2)
+            + self.losses as f64 * mean.powi(2))
+            / games
+    }
+
+    /// Elo difference with a 95% error bar
+    /// The difference is infinite when one side scored every point
+    pub fn elo(&self) -> Option<EloEstimate> {
+        if self.games() == 0 {
+            return None;
+        }
+        let mean = self.score();
+        let margin = 1.96 * (self.variance() / self.games() as f64).sqrt();
+        let low = score_to_elo((mean - margin).max(0.0));
+        let high = score_to_elo((mean + margin).min(1.0));
+        Some(EloEstimate {
+            elo: score_to_elo(mean),
+            error: (high - low) / 2.0,
+        })
+    }
+
+    /// Log-likelihood ratio of `sprt.elo1` against `sprt.elo0`, using the normal approximation
+    pub fn llr(&self, sprt: &Sprt) -> f64 {
+        let variance = self.variance();
+        if self.games() == 0 || variance == 0.0 {
+            return 0.0;
+        }
+        let s0 = elo_to_score(sprt.elo0);
+        let s1 = elo_to_score(sprt.elo1);
+        self.games() as f64 * (s1 - s0) * (2.0 * self.score() - s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn sprt(&self, sprt: &Sprt) -> SprtDecision {
+        let llr = self.llr(sprt);
+        let (lower, upper) = sprt.bounds();
+        if llr >= upper {
+            SprtDecision::AcceptH1
+        } else if llr <= lower {
+            SprtDecision::AcceptH0
+        } else {
+            SprtDecision::Continue
+        }
+    }
+
+    fn add(&mut self, winner: Option<bool>) {
+        match winner {
+            Some(true) => self.wins += 1,
+            Some(false) => self.losses += 1,
+            None => self.draws += 1,
+        }
+    }
+}
+
+impl Display for MatchResult {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "+{} ={} -{}", self.wins, self.draws, self.losses)
+    }
+}
+
+pub fn score_to_elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+pub fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+pub struct Pairing {
+    pub first: usize,
+    pub second: usize,
+    pub result: MatchResult,
+}
+
+/// Every sequence of `plies` moves from the empty board
+pub fn openings(plies: u32) -> Vec<Vec<u32>> {
+    let mut openings = vec![vec![]];
+    for _ in 0..plies {
+        let mut next = Vec::new();
+        for opening in &openings {
+            let mut game = Connect4::new();
+            for &col in opening {
+                game.play(col);
+            }
+            for col in game.legal_moves() {
+                let mut longer = opening.clone();
+                longer.push(col);
+                next.push(longer);
+            }
+        }
+        openings = next;
+    }
+    openings
+}
+
+/// Play one game from `opening` and return the winner, if any
+/// Engines without a seed of their own get one derived from `seed`
+pub fn play_game(red: &EngineConfig, yellow: &EngineConfig, opening: &[u32], seed: u64) -> Option<Player> {
+    let mut game = Connect4::new();
+    for &col in opening {
+        game.play(col);
+    }
+    let mut red_engine = red.or_seed(derive_seed(seed, 0)).build().unwrap();
+    let mut yellow_engine = yellow.or_seed(derive_seed(seed, 1)).build().unwrap();
+    while game.is_someone_winning().is_none() && !game.is_draw() {
+        match game.get_turn() {
+            Player::Red => game.play_engine(red_engine.as_mut(), &red.limits()),
+            Player::Yellow => game.play_engine(yellow_engine.as_mut(), &yellow.limits()),
+        };
+    }
+    game.is_someone_winning()
+}
+
+pub fn run_tournament(config: &TournamentConfig) -> Vec<Pairing> {
+    let count = config.participants.len();
+    let pairs: Vec<(usize, usize)> = match config.format {
+        Format::RoundRobin => (0..count)
+            .flat_map(|i| (i + 1..count).map(move |j| (i, j)))
+            .collect(),
+        Format::Gauntlet => (1..count).map(|j| (0, j)).collect(),
+    };
+
+    // Each opening is played twice per pairing, with colors swapped
+    let openings = openings(config.opening_plies);
+    let mut jobs = Vec::new();
+    for pair in 0..pairs.len() {
+        for opening in &openings {
+            jobs.push((pair, opening, false));
+            jobs.push((pair, opening, true));
+        }
+    }
+
+    let results = Mutex::new(vec![MatchResult::default(); pairs.len()]);
+    let next_job = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        for _ in 0..config.threads.max(1) {
+            scope.spawn(|| {
+                loop {
+                    let job = next_job.fetch_add(1, Ordering::Relaxed);
+                    let Some(&(pair, opening, swapped)) = jobs.get(job) else {
+                        break;
+                    };
+                    let seed = derive_seed(config.seed, job as u64);
+                    let (first, second) = pairs[pair];
+                    let first = &config.participants[first];
+                    let second = &config.participants[second];
+                    let winner = if swapped {
+                        play_game(second, first, opening, seed).map(|player| player == Player::Yellow)
+                    } else {
+                        play_game(first, second, opening, seed).map(|player| player == Player::Red)
+                    };
+                    results.lock().unwrap()[pair].add(winner);
+                }
+            });
+        }
+    });
+
+    pairs
+        .into_iter()
+        .zip(results.into_inner().unwrap())
+        .map(|((first, second), result)| Pairing { first, second, result })
+        .collect()
+}
+
+/// Elo rating of every participant explaining all the pairings at once, the first one being rated 0
+/// Each pairing counts an extra draw, so that a participant scoring every point keeps a finite rating
+pub fn fit_ratings(count: usize, pairings: &[Pairing]) -> Vec<f64> {
+    // Minorization-maximization of the Bradley-Terry likelihood, on strengths 10^(elo / 400)
+    let mut strengths = vec![1.0; count];
+    for _ in 0..1000 {
+        let mut next = vec![0.0; count];
+        for (i, strength) in next.iter_mut().enumerate() {
+            let mut points = 0.0;
+            let mut weight = 0.0;
+            for pairing in pairings {
+                let result = &pairing.result;
+                let games = result.games() as f64 + 1.0;
+                let first_points = result.wins as f64 + (result.draws as f64 + 1.0) / 2.0;
+                let (own_points, other) = if pairing.first == i {
+                    (first_points, pairing.second)
+                } else if pairing.second == i {
+                    (games - first_points, pairing.first)
+                } else {
+                    continue;
+                };
+                points += own_points;
+                weight += games / (strengths[i] + strengths[other]);
+            }
+            *strength = if weight > 0.0 { points / weight } else { strengths[i] };
+        }
+        strengths = next;
+    }
+    strengths.iter().map(|strength| 400.0 * (strength / strengths[0]).log10()).collect()
+}
+
+pub fn print_report(config: &TournamentConfig, pairings: &[Pairing]) {
+    for pairing in pairings {
+        let first = &config.participants[pairing.first];
+        let second = &config.participants[pairing.second];
+        let result = &pairing.result;
+        print!("{} vs {}: {} ({:.1}%)", first, second, result, result.score() * 100.0);
+        if let Some(estimate) = result.elo() {
+            print!(", Elo {:+.1}", estimate.elo);
+            // The error bar is unbounded when one side scored (almost) every point
+            if estimate.error.is_finite() {
+                print!(" +/- {:.1}", estimate.error);
+            }
+        }
+        println!();
+
+        if let Some(sprt) = &config.sprt {
+            let (lower, upper) = sprt.bounds();
+            println!(
+                "  SPRT [{}, {}]: LLR {:.2} ({:.2}, {:.2}) {:?}",
+                sprt.elo0,
+                sprt.elo1,
+                result.llr(sprt),
+                lower,
+                upper,
+                result.sprt(sprt)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_ratings() {
+        // 76% is about 200 Elo, a chain of them adds up
+        let result = MatchResult { wins: 76, draws: 0, losses: 24 };
+        let pairings = [Pairing { first: 1, second: 0, result }, Pairing { first: 2, second: 1, result }];
+        let ratings = fit_ratings(3, &pairings);
+        assert_eq!(ratings[0], 0.0);
+        assert!((ratings[1] - 200.0).abs() < 20.0, "{:?}", ratings);
+        assert!((ratings[2] - 400.0).abs() < 40.0, "{:?}", ratings);
+
+        // Every point scored still gives a finite rating
+        let sweep = MatchResult { wins: 10, draws: 0, losses: 0 };
+        let ratings = fit_ratings(2, &[Pairing { first: 0, second: 1, result: sweep }]);
+        assert!(ratings[1].is_finite() && ratings[1] < -300.0);
+    }
+
+    #[test]
+    fn test_openings() {
+        assert_eq!(openings(0).len(), 1);
+        assert_eq!(openings(2).len(), 49);
+    }
+
+    #[test]
+    fn test_elo() {
+        let even = MatchResult { wins: 10, draws: 0, losses: 10 };
+        assert!(even.elo().unwrap().elo.abs() < 1e-9);
+
+        let better = MatchResult { wins: 30, draws: 10, losses: 10 };
+        let estimate = better.elo().unwrap();
+        assert!(estimate.elo > 0.0);
+        assert!(estimate.error > 0.0);
+    }
+
+    #[test]
+    fn test_sprt() {
+        let sprt = Sprt::new(0.0, 50.0);
+        let winning = MatchResult { wins: 300, draws: 50, losses: 100 };
+        assert_eq!(winning.sprt(&sprt), SprtDecision::AcceptH1);
+        let losing = MatchResult { wins: 100, draws: 50, losses: 300 };
+        assert_eq!(losing.sprt(&sprt), SprtDecision::AcceptH0);
+    }
+}
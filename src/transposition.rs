@@ -0,0 +1,249 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+/// Default size of a transposition table, in megabytes
+pub const DEFAULT_HASH_MB: usize = 16;
+
+const NO_MOVE: u64 = 0xFF;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeType {
+    Exact,
+    LowerBound,
+    UpperBound,
+}
+
+/// What the table knows about a position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TranspositionEntry {
+    pub score: i32,
+    pub depth: i32,
+    pub node_type: NodeType,
+    pub best_move: Option<u32>,
+}
+
+/// An entry packed in a single word, next to its key xored with that word
+/// A slot torn by two threads writing at once fails the key check and reads as empty
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+/// Unpacked slot data, depth 0 marks an empty slot
+struct SlotData {
+    score: i32,
+    depth: u64,
+    node_type: NodeType,
+    best_move: u64,
+    age: u8,
+}
+
+impl SlotData {
+    fn pack(&self) -> u64 {
+        let node_type = match self.node_type {
+            NodeType::Exact => 0,
+            NodeType::LowerBound => 1,
+            NodeType::UpperBound => 2,
+        };
+        (self.score as u32 as u64)
+            | self.depth << 32
+            | node_type << 40
+            | self.best_move << 42
+            | (self.age as u64) << 50
+    }
+
+    fn unpack(data: u64) -> SlotData {
+        SlotData {
+            score: data as u32 as i32,
+            depth: (data >> 32) & 0xFF,
+            node_type: match (data >> 40) & 0b11 {
+                0 => NodeType::Exact,
+                1 => NodeType::LowerBound,
+                _ => NodeType::UpperBound,
+            },
+            best_move: (data >> 42) & 0xFF,
+            age: (data >> 50) as u8,
+        }
+    }
+}
+
+impl Slot {
+    /// Data of the slot if it holds `key`
+    fn read(&self, key: u64) -> Option<SlotData> {
+        let data = self.data.load(Ordering::Relaxed);
+        let stored_key = self.key.load(Ordering::Relaxed) ^ data;
+        let slot = SlotData::unpack(data);
+        (stored_key == key && slot.depth > 0).then_some(slot)
+    }
+
+    fn write(&self, key: u64, slot: &SlotData) {
+        let data = slot.pack();
+        self.data.store(data, Ordering::Relaxed);
+        self.key.store(key ^ data, Ordering::Relaxed);
+    }
+}
+
+/// Number of slots a position can be stored in
+const BUCKET_SIZE: usize = 2;
+
+/// Fixed-size transposition table, shared without locks by the threads of a parallel search
+/// Each position maps to a bucket of two slots. When both are taken, the entry of an older search
+/// is replaced first, then the shallowest one.
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    age: AtomicU8,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let wanted = size_mb * 1024 * 1024 / std::mem::size_of::<Slot>();
+        // Keep a power of two number of buckets to index with a mask
+        let buckets = (wanted / BUCKET_SIZE).max(1);
+        let buckets = if buckets.is_power_of_two() {
+            buckets
+        } else {
+            buckets.next_power_of_two() / 2
+        };
+        TranspositionTable {
+            slots: (0..buckets * BUCKET_SIZE).map(|_| Slot::default()).collect(),
+            age: AtomicU8::new(0),
+        }
+    }
+
+    /// Size of the table in megabytes
+    pub fn size_mb(&self) -> usize {
+        self.slots.len() * std::mem::size_of::<Slot>() / (1024 * 1024)
+    }
+
+    /// Mark the start of a new search, entries of previous searches become the first to go
+    pub fn new_search(&self) {
+        self.age.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TranspositionEntry> {
+        self.bucket(key)
+            .iter()
+            .find_map(|slot| slot.read(key))
+            .map(|slot| TranspositionEntry {
+                score: slot.score,
+                depth: slot.depth as i32 - 1,
+                node_type: slot.node_type,
+                best_move: (slot.best_move != NO_MOVE).then_some(slot.best_move as u32),
+            })
+    }
+
+    pub fn store(&self, key: u64, score: i32, depth: i32, node_type: NodeType, best_move: Option<u32>) {
+        let age = self.age.load(Ordering::Relaxed);
+        let bucket = self.bucket(key);
+        let best_move = best_move.map_or(NO_MOVE, |col| col as u64);
+        // Depths are stored one higher, 0 being an empty slot
+        let depth = depth.clamp(0, 254) as u64 + 1;
+
+        let slot = match bucket.iter().find(|slot| slot.read(key).is_some()) {
+            Some(slot) => slot,
+            None => {
+                // Empty slots go first, then entries of previous searches, then the current search's,
+                // the shallowest first within each
+                let worth = |slot: &Slot| {
+                    let data = SlotData::unpack(slot.data.load(Ordering::Relaxed));
+                    if data.age == age { 256 + data.depth } else { data.depth }
+                };
+                bucket.iter().min_by_key(|slot| worth(slot)).unwrap()
+            }
+        };
+
+        // Keep a deeper result of the same search for the same position, but remember its best move
+        if let Some(existing) = slot.read(key) {
+            if existing.age == age && existing.depth > depth {
+                if existing.best_move == NO_MOVE && best_move != NO_MOVE {
+                    slot.write(key, &SlotData { best_move, ..existing });
+                }
+                return;
+            }
+        }
+
+        slot.write(key, &SlotData { score, depth, node_type, best_move, age });
+    }
+
+    fn bucket(&self, key: u64) -> &[Slot] {
+        let buckets = self.slots.len() / BUCKET_SIZE;
+        let start = (key as usize & (buckets - 1)) * BUCKET_SIZE;
+        &self.slots[start..start + BUCKET_SIZE]
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_HASH_MB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_probe() {
+        let table = TranspositionTable::new(1);
+        assert_eq!(table.size_mb(), 1);
+        table.store(42, 7, 3, NodeType::Exact, Some(2));
+        let entry = table.probe(42).unwrap();
+        assert_eq!(entry.score, 7);
+        assert_eq!(entry.depth, 3);
+        assert_eq!(entry.best_move, Some(2));
+        assert_eq!(table.probe(43), None);
+    }
+
+    #[test]
+    fn test_replacement_prefers_depth_then_age() {
+        let table = TranspositionTable::new(1);
+        let buckets = (table.slots.len() / BUCKET_SIZE) as u64;
+        // Three keys falling in the same bucket
+        let (a, b, c) = (5, 5 + buckets, 5 + 2 * buckets);
+
+        table.store(a, 1, 8, NodeType::Exact, None);
+        table.store(b, 2, 2, NodeType::Exact, None);
+        table.store(c, 3, 4, NodeType::Exact, None);
+        assert!(table.probe(a).is_some());
+        assert!(table.probe(b).is_none());
+
+        // A new search makes both replaceable, the shallower one first
+        table.new_search();
+        table.store(b, 2, 1, NodeType::Exact, None);
+        assert!(table.probe(a).is_some());
+        assert!(table.probe(c).is_none());
+        // Even a shallow entry of the current search outlives a deep stale one
+        let d = 5 + 3 * buckets;
+        table.store(d, 4, 1, NodeType::Exact, None);
+        assert!(table.probe(a).is_none());
+        assert_eq!(table.probe(b).unwrap().score, 2);
+        assert_eq!(table.probe(d).unwrap().score, 4);
+    }
+
+    #[test]
+    fn test_negative_scores_and_shared_use() {
+        let table = TranspositionTable::new(1);
+        std::thread::scope(|scope| {
+            for thread in 0..4u64 {
+                let table = &table;
+                scope.spawn(move || {
+                    for key in 0..1000 {
+                        table.store(key * 4 + thread, -(key as i32), 2, NodeType::UpperBound, None);
+                    }
+                });
+            }
+        });
+        let entry = table.probe(4 * 999 + 3).unwrap();
+        assert_eq!(entry.score, -999);
+        assert_eq!(entry.node_type, NodeType::UpperBound);
+        assert_eq!(entry.best_move, None);
+    }
+}
@@ -0,0 +1,186 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::engine::{derive_seed, is_decisive, EngineConfig, EvalParams};
+use crate::evaluator::evaluate_board;
+use crate::solver::Solver;
+use crate::Connect4;
+
+/// Where the expected result of a training position comes from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Label {
+    /// Result of the self-play game the position comes from
+    Result,
+    /// Solver value, positions too deep to be solved quickly are dropped
+    Solver,
+}
+
+impl std::str::FromStr for Label {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "result" => Ok(Label::Result),
+            "solver" => Ok(Label::Solver),
+            _ => Err(format!("Unknown label: {}", s)),
+        }
+    }
+}
+
+/// Nodes the solver may spend labelling one position
+const SOLVER_LABEL_NODES: u64 = 200_000;
+
+pub struct TuneConfig {
+    /// Self-play games generating the positions
+    pub games: usize,
+    /// Random moves opening each game, for variety
+    pub random_plies: usize,
+    /// Engine playing both sides
+    pub engine: EngineConfig,
+    pub label: Label,
+    /// Passes over every weight, the search stops earlier once no step helps
+    pub iterations: usize,
+    pub seed: u64,
+}
+
+/// A position and the expected score of the player to move: 1 for a win, 0.5 for a draw, 0 for a loss
+pub struct Sample {
+    pub game: Connect4,
+    pub result: f64,
+}
+
+/// Play `config.games` games and keep the positions the evaluation has to estimate
+pub fn generate_samples(config: &TuneConfig) -> Vec<Sample> {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut solver = Solver::new();
+    let mut samples = Vec::new();
+
+    for index in 0..config.games {
+        let mut game = Connect4::new();
+        for _ in 0..config.random_plies {
+            let moves: Vec<u32> = game.legal_moves().collect();
+            game.play(*moves.choose(&mut rng).unwrap());
+        }
+        if game_over(&game) {
+            continue;
+        }
+
+        let mut engine = config.engine.or_seed(derive_seed(config.seed, index as u64)).build().unwrap();
+        let mut positions = Vec::new();
+        while !game_over(&game) {
+            // Positions with a forced result are scored exactly, the weights play no part there
+            if !is_decisive(evaluate_board(&game, &EvalParams::default())) {
+                positions.push(game.clone());
+            }
+            game.play_engine(engine.as_mut(), &config.engine.limits());
+        }
+
+        let winner = game.is_someone_winning();
+        for position in positions {
+            let result = match config.label {
+                Label::Result => match winner {
+                    Some(player) if player == *position.get_turn() => 1.0,
+                    Some(_) => 0.0,
+                    None => 0.5,
+                },
+                Label::Solver => match solver.try_solve(&position, SOLVER_LABEL_NODES) {
+                    Some(score) => (score.signum() as f64 + 1.0) / 2.0,
+                    None => continue,
+                },
+            };
+            samples.push(Sample { game: position, result });
+        }
+    }
+    samples
+}
+
+fn game_over(game: &Connect4) -> bool {
+    game.is_someone_winning().is_some() || game.is_draw()
+}
+
+fn sigmoid(score: i32, scale: f64) -> f64 {
+    1.0 / (1.0 + (-scale * score as f64).exp())
+}
+
+/// Mean squared error between the results and the evaluation mapped to an expected score
+pub fn error(samples: &[Sample], params: &EvalParams, scale: f64) -> f64 {
+    let total: f64 = samples
+        .iter()
+        .map(|sample| (sample.result - sigmoid(evaluate_board(&sample.game, params), scale)).powi(2))
+        .sum();
+    total / samples.len().max(1) as f64
+}
+
+/// Scale turning evaluations into expected scores that fits `params` best
+pub fn fit_scale(samples: &[Sample], params: &EvalParams) -> f64 {
+    (1..=200)
+        .map(|step| step as f64 * 0.001)
+        .min_by(|&a, &b| error(samples, params, a).total_cmp(&error(samples, params, b)))
+        .unwrap()
+}
+
+/// Texel-style local search: move each weight up or down by one while the error drops
+/// Weights stay within the bounds of `EvalParams::validate`
+/// The scale is fitted once for the starting weights and kept, so the weights can't all just shrink
+/// Returns the best weights and their error, `verbose` printing the error after each pass
+pub fn tune(samples: &[Sample], start: EvalParams, iterations: usize, verbose: bool) -> (EvalParams, f64) {
+    let scale = fit_scale(samples, &start);
+    let mut best = start;
+    let mut best_error = error(samples, &best, scale);
+
+    for iteration in 0..iterations {
+        let mut improved = false;
+        for weight in 0..EvalParams::WEIGHTS {
+            for step in [1, -1] {
+                let mut candidate = best;
+                *candidate.weights_mut()[weight] += step;
+                if candidate.validate().is_err() {
+                    continue;
+                }
+                let candidate_error = error(samples, &candidate, scale);
+                if candidate_error < best_error {
+                    best = candidate;
+                    best_error = candidate_error;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+        if verbose {
+            println!("Iteration {}: error {:.6}", iteration + 1, best_error);
+        }
+        if !improved {
+            break;
+        }
+    }
+    (best, best_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tuning_fixes_bad_weight() {
+        let config = TuneConfig {
+            games: 4,
+            random_plies: 4,
+            engine: "novice".parse().unwrap(),
+            label: Label::Result,
+            iterations: 5,
+            seed: 3,
+        };
+        let samples = generate_samples(&config);
+        assert!(!samples.is_empty());
+        assert!(samples.iter().all(|sample| [0.0, 0.5, 1.0].contains(&sample.result)));
+
+        // Penalizing the threats that win the endgame goes against the results, tuning has to undo some of it
+        let start = EvalParams { good_threat: -20, ..EvalParams::default() };
+        let scale = fit_scale(&samples, &start);
+        let (tuned, tuned_error) = tune(&samples, start, config.iterations, false);
+        assert!(tuned.good_threat > start.good_threat, "{:?}", tuned);
+        assert!(tuned.validate().is_ok());
+        assert!(tuned_error < error(&samples, &start, scale));
+    }
+}